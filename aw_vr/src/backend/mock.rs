@@ -0,0 +1,145 @@
+//! A scripted backend, so the hooks' frame logic can be tested without a headset.
+
+use std::collections::VecDeque;
+
+use timing::FrameStats;
+use super::{Error, Eye, EyeTextures, Fov, FrameStart, InputState, Pose, VrBackend};
+
+/// A `VrBackend` call, as recorded by `MockBackend`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Call {
+    BeginFrame,
+    EyePoses,
+    EyeFov(Eye),
+    RecommendedSize,
+    CreateSwapChains(u32, u32),
+    Acquire(Eye),
+    Commit(Eye),
+    Submit,
+    PollInput,
+    Recenter,
+}
+
+pub struct MockBackend {
+    /// Every call made so far, in order.
+    pub calls: Vec<Call>,
+    /// Poses handed out by successive `eye_poses` calls. The last one repeats.
+    pub poses: VecDeque<[Pose; 2]>,
+    /// Input handed out by successive `poll_input` calls. Idle once empty.
+    pub input: VecDeque<InputState>,
+    pub fov: [Fov; 2],
+    /// The eye buffer size at a pixel density of 1.
+    pub size: (u32, u32),
+    /// Whether `begin_frame` wants the frame rendered.
+    pub should_render: bool,
+    /// Whether the next `begin_frame` reports a recenter by the runtime.
+    pub recentered: bool,
+    /// Whether eyes get a depth texture as well as a color one.
+    pub depth: bool,
+    /// What `set_depth_range` was last given.
+    pub depth_range: Option<(f32, f32)>,
+    last_poses: [Pose; 2],
+    swap_chains: bool,
+    next_texture: u32,
+}
+
+impl MockBackend {
+    pub fn new() -> MockBackend {
+        MockBackend {
+            calls: Vec::new(),
+            poses: VecDeque::new(),
+            input: VecDeque::new(),
+            fov: [Fov::square(), Fov::square()],
+            size: (1000, 1100),
+            should_render: true,
+            recentered: false,
+            depth: false,
+            depth_range: None,
+            last_poses: [Pose::identity(), Pose::identity()],
+            swap_chains: false,
+            next_texture: 0,
+        }
+    }
+
+    /// Returns the calls recorded so far, and forgets them.
+    pub fn take_calls(&mut self) -> Vec<Call> {
+        self.calls.split_off(0)
+    }
+}
+
+impl VrBackend for MockBackend {
+    fn name(&self) -> &'static str {
+        "Mock"
+    }
+
+    fn begin_frame(&mut self) -> Result<FrameStart, Error> {
+        self.calls.push(Call::BeginFrame);
+        let recentered = std::mem::replace(&mut self.recentered, false);
+        Ok(FrameStart { render: self.should_render, recentered })
+    }
+
+    fn eye_poses(&mut self) -> Result<[Pose; 2], Error> {
+        self.calls.push(Call::EyePoses);
+        if let Some(poses) = self.poses.pop_front() {
+            self.last_poses = poses;
+        }
+        Ok(self.last_poses)
+    }
+
+    fn eye_fov(&mut self, eye: Eye) -> Result<Fov, Error> {
+        self.calls.push(Call::EyeFov(eye));
+        Ok(self.fov[eye.index()])
+    }
+
+    fn recommended_size(&mut self, pixel_density: f32) -> Result<(u32, u32), Error> {
+        self.calls.push(Call::RecommendedSize);
+        let (width, height) = self.size;
+        Ok(((width as f32 * pixel_density) as u32, (height as f32 * pixel_density) as u32))
+    }
+
+    fn create_swap_chains(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        self.calls.push(Call::CreateSwapChains(width, height));
+        self.swap_chains = true;
+        Ok(())
+    }
+
+    fn acquire_eye_textures(&mut self, eye: Eye) -> Result<EyeTextures, Error> {
+        self.calls.push(Call::Acquire(eye));
+        if !self.swap_chains {
+            return Err(Error::Call { call: "acquire_eye_textures", code: -1 });
+        }
+        self.next_texture += 1;
+        Ok(EyeTextures {
+            color: self.next_texture,
+            depth: if self.depth { Some(self.next_texture + 1000) } else { None },
+        })
+    }
+
+    fn commit(&mut self, eye: Eye) -> Result<(), Error> {
+        self.calls.push(Call::Commit(eye));
+        Ok(())
+    }
+
+    fn set_depth_range(&mut self, range: Option<(f32, f32)>) {
+        self.depth_range = range;
+    }
+
+    fn submit(&mut self) -> Result<(), Error> {
+        self.calls.push(Call::Submit);
+        Ok(())
+    }
+
+    fn take_frame_stats(&mut self) -> FrameStats {
+        FrameStats::default()
+    }
+
+    fn poll_input(&mut self) -> Result<InputState, Error> {
+        self.calls.push(Call::PollInput);
+        Ok(self.input.pop_front().unwrap_or_default())
+    }
+
+    fn recenter(&mut self) -> Result<(), Error> {
+        self.calls.push(Call::Recenter);
+        Ok(())
+    }
+}
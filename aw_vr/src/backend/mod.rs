@@ -0,0 +1,259 @@
+//! Headset runtimes AW_VR can render to.
+//!
+//! The RenderWare hooks only talk to a `VrBackend`. Which runtime sits behind
+//! it is picked when the first frame is drawn: LibOVR by default, or OpenXR
+//! when `AW_VR_BACKEND=openxr` is set in AW's environment or the injector asks
+//! for it.
+
+#[cfg(test)]
+pub mod mock;
+pub mod openxr;
+pub mod ovr;
+
+use std::env;
+use std::fmt;
+
+use gl;
+use timing::FrameStats;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    pub fn index(self) -> usize {
+        match self {
+            Eye::Left => 0,
+            Eye::Right => 1,
+        }
+    }
+
+    pub fn from_index(index: usize) -> Eye {
+        if index & 1 == 0 {
+            Eye::Left
+        } else {
+            Eye::Right
+        }
+    }
+}
+
+/// A tracked pose in meters, with the orientation as an `(x, y, z, w)` quaternion.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pose {
+    pub position: [f32; 3],
+    pub orientation: [f32; 4],
+}
+
+impl Pose {
+    pub fn identity() -> Pose {
+        Pose {
+            position: [0.0, 0.0, 0.0],
+            orientation: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// A pose turned by `yaw` radians about the vertical axis.
+    pub fn from_yaw(yaw: f32, position: [f32; 3]) -> Pose {
+        let half = yaw / 2.0;
+        Pose {
+            position,
+            orientation: [0.0, half.sin(), 0.0, half.cos()],
+        }
+    }
+
+    /// Rotation about the vertical axis, in radians, counter-clockwise seen from above.
+    pub fn yaw(&self) -> f32 {
+        let [x, y, z, w] = self.orientation;
+        (2.0 * (w * y + x * z)).atan2(1.0 - 2.0 * (x * x + y * y))
+    }
+
+    pub fn rotate(&self, v: [f32; 3]) -> [f32; 3] {
+        let [x, y, z, w] = self.orientation;
+        // v + 2w(u × v) + 2u × (u × v), with u the vector part of the quaternion.
+        let t = [2.0 * (y * v[2] - z * v[1]), 2.0 * (z * v[0] - x * v[2]), 2.0 * (x * v[1] - y * v[0])];
+        [
+            v[0] + w * t[0] + (y * t[2] - z * t[1]),
+            v[1] + w * t[1] + (z * t[0] - x * t[2]),
+            v[2] + w * t[2] + (x * t[1] - y * t[0]),
+        ]
+    }
+
+    /// `other` expressed in the space this pose describes, brought out into the parent space.
+    pub fn compose(&self, other: &Pose) -> Pose {
+        let [ax, ay, az, aw] = self.orientation;
+        let [bx, by, bz, bw] = other.orientation;
+        let offset = self.rotate(other.position);
+        Pose {
+            position: [self.position[0] + offset[0], self.position[1] + offset[1], self.position[2] + offset[2]],
+            orientation: [
+                aw * bx + ax * bw + ay * bz - az * by,
+                aw * by - ax * bz + ay * bw + az * bx,
+                aw * bz + ax * by - ay * bx + az * bw,
+                aw * bw - ax * bx - ay * by - az * bz,
+            ],
+        }
+    }
+}
+
+/// Where poses are measured from, before any recentering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackingOrigin {
+    /// On the floor, so tracked height is the headset's height above it.
+    Floor,
+    /// At the headset's height when the runtime started or last recentered.
+    Eye,
+}
+
+/// An eye's field of view, as the tangents of the angles from its center to each edge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fov {
+    pub up: f32,
+    pub down: f32,
+    pub left: f32,
+    pub right: f32,
+}
+
+impl Fov {
+    /// The symmetric 90° frustum AW_VR has always rendered with.
+    pub fn square() -> Fov {
+        Fov { up: 1.0, down: 1.0, left: 1.0, right: 1.0 }
+    }
+}
+
+/// The GL textures one eye is copied into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EyeTextures {
+    pub color: u32,
+    /// `None` if the runtime doesn't take depth.
+    pub depth: Option<u32>,
+}
+
+/// Button bits of `InputState::buttons`. These match LibOVR's `ovrButton` values.
+pub mod button {
+    pub const A: u32 = 0x0000_0001;
+    pub const B: u32 = 0x0000_0002;
+    pub const RTHUMB: u32 = 0x0000_0004;
+    pub const X: u32 = 0x0000_0100;
+    pub const Y: u32 = 0x0000_0200;
+    pub const LTHUMB: u32 = 0x0000_0400;
+    pub const ENTER: u32 = 0x0010_0000;
+}
+
+/// Touch bits of `InputState::touches`, for fingers resting on a control.
+/// These match LibOVR's `ovrTouch` values.
+pub mod touch {
+    pub const A: u32 = 0x0000_0001;
+    pub const B: u32 = 0x0000_0002;
+    pub const RTHUMB: u32 = 0x0000_0004;
+    pub const RTHUMB_REST: u32 = 0x0000_0008;
+    pub const RINDEX_TRIGGER: u32 = 0x0000_0010;
+    pub const X: u32 = 0x0000_0100;
+    pub const Y: u32 = 0x0000_0200;
+    pub const LTHUMB: u32 = 0x0000_0400;
+    pub const LTHUMB_REST: u32 = 0x0000_0800;
+    pub const LINDEX_TRIGGER: u32 = 0x0000_1000;
+}
+
+/// What `begin_frame` found.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStart {
+    /// Whether the runtime wants the frame rendered.
+    pub render: bool,
+    /// Whether the runtime recentered the tracking origin since the last
+    /// frame, as the system's own recenter does.
+    pub recentered: bool,
+}
+
+/// Touch controller state, indexed `[left, right]` where a value is per hand.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InputState {
+    pub buttons: u32,
+    pub touches: u32,
+    pub index_trigger: [f32; 2],
+    pub hand_trigger: [f32; 2],
+    pub thumbstick: [[f32; 2]; 2],
+    /// The controllers' poses, in meters from the tracking origin, while
+    /// they're tracked.
+    pub hands: [Option<Pose>; 2],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The runtime library couldn't be loaded, or lacks an entry point.
+    Load(String),
+    /// A runtime call failed with the given result code.
+    Call { call: &'static str, code: i32 },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Load(ref what) => write!(f, "unable to load VR runtime: {}", what),
+            Error::Call { call, code } => write!(f, "{} failed with result {}", call, code),
+        }
+    }
+}
+
+/// A headset runtime.
+///
+/// A stereo frame is `begin_frame`, `eye_poses` and `eye_fov`, then for each eye
+/// `acquire_eye_textures` and `commit` once RenderWare has drawn it, and finally
+/// `submit`. `submit` must be called even when the frame isn't to be rendered.
+pub trait VrBackend: Send {
+    fn name(&self) -> &'static str;
+
+    /// Starts a stereo frame.
+    fn begin_frame(&mut self) -> Result<FrameStart, Error>;
+
+    /// The eye poses for the current frame, in meters from the tracking origin.
+    fn eye_poses(&mut self) -> Result<[Pose; 2], Error>;
+
+    /// The field of view the given eye should be rendered with.
+    fn eye_fov(&mut self, eye: Eye) -> Result<Fov, Error>;
+
+    /// The eye buffer size the runtime recommends for its default FOV, scaled
+    /// by `pixel_density`. Both eyes share the larger of their two sizes.
+    fn recommended_size(&mut self, pixel_density: f32) -> Result<(u32, u32), Error>;
+
+    /// (Re)creates the per-eye color swap chains, and depth ones if the runtime
+    /// takes depth.
+    fn create_swap_chains(&mut self, width: u32, height: u32) -> Result<(), Error>;
+
+    /// Returns the GL textures the given eye should be copied into.
+    fn acquire_eye_textures(&mut self, eye: Eye) -> Result<EyeTextures, Error>;
+
+    fn commit(&mut self, eye: Eye) -> Result<(), Error>;
+
+    /// The near and far clip distances, in meters, the eyes are being drawn
+    /// with. Depth is only submitted while this is known.
+    fn set_depth_range(&mut self, range: Option<(f32, f32)>);
+
+    /// Hands the frame's eye textures to the compositor, rendered from the poses
+    /// last returned by `eye_poses`.
+    fn submit(&mut self) -> Result<(), Error>;
+
+    /// Frame timing since this was last called.
+    fn take_frame_stats(&mut self) -> FrameStats;
+
+    fn poll_input(&mut self) -> Result<InputState, Error>;
+
+    /// Makes the current head position and heading the new tracking origin.
+    fn recenter(&mut self) -> Result<(), Error>;
+}
+
+/// Creates the backend selected by `AW_VR_BACKEND`, or OpenXR if
+/// `use_openxr`, tracking from `origin`. Must be called on AW's render thread,
+/// since OpenXR shares the current GL context.
+pub fn create(origin: TrackingOrigin, use_openxr: bool) -> Result<Box<dyn VrBackend>, Error> {
+    let use_openxr = use_openxr || env::var("AW_VR_BACKEND").map(|name| name.eq_ignore_ascii_case("openxr")).unwrap_or(false);
+    if use_openxr {
+        let loader = env::var("AW_VR_OPENXR_LOADER").unwrap_or_else(|_| openxr::DEFAULT_LOADER.to_owned());
+        let (dc, glrc) = gl::current_context().map_err(|error| Error::Load(error.to_string()))?;
+        Ok(Box::new(openxr::OpenXrBackend::new(&loader, origin, dc, glrc)?))
+    } else {
+        Ok(Box::new(ovr::OvrBackend::new(origin)?))
+    }
+}
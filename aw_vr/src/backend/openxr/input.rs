@@ -0,0 +1,262 @@
+//! Touch controller input through OpenXR actions.
+
+use std::ffi::CString;
+use std::ptr;
+
+use backend::{button, touch, Error, InputState, Pose};
+
+use super::{check, copy_name, sys, to_pose, to_posef, Functions};
+
+const TOUCH_CONTROLLER: &str = "/interaction_profiles/oculus/touch_controller";
+
+pub struct Actions {
+    set: sys::ActionSet,
+    hands: [sys::Path; 2],
+    thumbstick: sys::Action,
+    thumbstick_click: sys::Action,
+    trigger: sys::Action,
+    squeeze: sys::Action,
+    a: sys::Action,
+    b: sys::Action,
+    x: sys::Action,
+    y: sys::Action,
+    menu: sys::Action,
+    thumbstick_touch: sys::Action,
+    trigger_touch: sys::Action,
+    thumbrest_touch: sys::Action,
+    a_touch: sys::Action,
+    b_touch: sys::Action,
+    x_touch: sys::Action,
+    y_touch: sys::Action,
+    aim: sys::Action,
+    /// Where each controller points, from `aim`.
+    aim_spaces: [sys::Space; 2],
+}
+
+unsafe fn path(fns: &Functions, instance: sys::Instance, name: &str) -> Result<sys::Path, Error> {
+    let name = CString::new(name).unwrap();
+    let mut path = sys::NULL_PATH;
+    check("xrStringToPath", (fns.xrStringToPath)(instance, name.as_ptr(), &mut path))?;
+    Ok(path)
+}
+
+unsafe fn action(fns: &Functions, set: sys::ActionSet, name: &str, kind: i32, hands: &[sys::Path]) -> Result<sys::Action, Error> {
+    let mut info = sys::ActionCreateInfo {
+        ty: sys::TYPE_ACTION_CREATE_INFO,
+        next: ptr::null(),
+        actionName: [0; sys::MAX_ACTION_NAME_SIZE],
+        actionType: kind,
+        countSubactionPaths: hands.len() as u32,
+        subactionPaths: hands.as_ptr(),
+        localizedActionName: [0; sys::MAX_LOCALIZED_ACTION_NAME_SIZE],
+    };
+    copy_name(&mut info.actionName, name);
+    copy_name(&mut info.localizedActionName, name);
+    let mut action = sys::NULL_HANDLE;
+    check("xrCreateAction", (fns.xrCreateAction)(set, &info, &mut action))?;
+    Ok(action)
+}
+
+impl Actions {
+    /// Creates AW_VR's action set, suggests Touch controller bindings for it
+    /// and attaches it to `session`.
+    pub unsafe fn new(fns: &Functions, instance: sys::Instance, session: sys::Session) -> Result<Actions, Error> {
+        let mut info = sys::ActionSetCreateInfo {
+            ty: sys::TYPE_ACTION_SET_CREATE_INFO,
+            next: ptr::null(),
+            actionSetName: [0; sys::MAX_ACTION_SET_NAME_SIZE],
+            localizedActionSetName: [0; sys::MAX_LOCALIZED_ACTION_SET_NAME_SIZE],
+            priority: 0,
+        };
+        copy_name(&mut info.actionSetName, "aw_vr");
+        copy_name(&mut info.localizedActionSetName, "AW_VR");
+        let mut set = sys::NULL_HANDLE;
+        check("xrCreateActionSet", (fns.xrCreateActionSet)(instance, &info, &mut set))?;
+
+        let hands = [path(fns, instance, "/user/hand/left")?, path(fns, instance, "/user/hand/right")?];
+        let mut actions = Actions {
+            set,
+            hands,
+            thumbstick: action(fns, set, "thumbstick", sys::ACTION_TYPE_VECTOR2F_INPUT, &hands)?,
+            thumbstick_click: action(fns, set, "thumbstick_click", sys::ACTION_TYPE_BOOLEAN_INPUT, &hands)?,
+            trigger: action(fns, set, "trigger", sys::ACTION_TYPE_FLOAT_INPUT, &hands)?,
+            squeeze: action(fns, set, "squeeze", sys::ACTION_TYPE_FLOAT_INPUT, &hands)?,
+            a: action(fns, set, "a", sys::ACTION_TYPE_BOOLEAN_INPUT, &[])?,
+            b: action(fns, set, "b", sys::ACTION_TYPE_BOOLEAN_INPUT, &[])?,
+            x: action(fns, set, "x", sys::ACTION_TYPE_BOOLEAN_INPUT, &[])?,
+            y: action(fns, set, "y", sys::ACTION_TYPE_BOOLEAN_INPUT, &[])?,
+            menu: action(fns, set, "menu", sys::ACTION_TYPE_BOOLEAN_INPUT, &[])?,
+            thumbstick_touch: action(fns, set, "thumbstick_touch", sys::ACTION_TYPE_BOOLEAN_INPUT, &hands)?,
+            trigger_touch: action(fns, set, "trigger_touch", sys::ACTION_TYPE_BOOLEAN_INPUT, &hands)?,
+            thumbrest_touch: action(fns, set, "thumbrest_touch", sys::ACTION_TYPE_BOOLEAN_INPUT, &hands)?,
+            a_touch: action(fns, set, "a_touch", sys::ACTION_TYPE_BOOLEAN_INPUT, &[])?,
+            b_touch: action(fns, set, "b_touch", sys::ACTION_TYPE_BOOLEAN_INPUT, &[])?,
+            x_touch: action(fns, set, "x_touch", sys::ACTION_TYPE_BOOLEAN_INPUT, &[])?,
+            y_touch: action(fns, set, "y_touch", sys::ACTION_TYPE_BOOLEAN_INPUT, &[])?,
+            aim: action(fns, set, "aim", sys::ACTION_TYPE_POSE_INPUT, &hands)?,
+            aim_spaces: [sys::NULL_HANDLE; 2],
+        };
+
+        let suggestions = [
+            (actions.thumbstick, "/user/hand/left/input/thumbstick"),
+            (actions.thumbstick, "/user/hand/right/input/thumbstick"),
+            (actions.thumbstick_click, "/user/hand/left/input/thumbstick/click"),
+            (actions.thumbstick_click, "/user/hand/right/input/thumbstick/click"),
+            (actions.trigger, "/user/hand/left/input/trigger/value"),
+            (actions.trigger, "/user/hand/right/input/trigger/value"),
+            (actions.squeeze, "/user/hand/left/input/squeeze/value"),
+            (actions.squeeze, "/user/hand/right/input/squeeze/value"),
+            (actions.a, "/user/hand/right/input/a/click"),
+            (actions.b, "/user/hand/right/input/b/click"),
+            (actions.x, "/user/hand/left/input/x/click"),
+            (actions.y, "/user/hand/left/input/y/click"),
+            (actions.menu, "/user/hand/left/input/menu/click"),
+            (actions.thumbstick_touch, "/user/hand/left/input/thumbstick/touch"),
+            (actions.thumbstick_touch, "/user/hand/right/input/thumbstick/touch"),
+            (actions.trigger_touch, "/user/hand/left/input/trigger/touch"),
+            (actions.trigger_touch, "/user/hand/right/input/trigger/touch"),
+            (actions.thumbrest_touch, "/user/hand/left/input/thumbrest/touch"),
+            (actions.thumbrest_touch, "/user/hand/right/input/thumbrest/touch"),
+            (actions.a_touch, "/user/hand/right/input/a/touch"),
+            (actions.b_touch, "/user/hand/right/input/b/touch"),
+            (actions.x_touch, "/user/hand/left/input/x/touch"),
+            (actions.y_touch, "/user/hand/left/input/y/touch"),
+            (actions.aim, "/user/hand/left/input/aim/pose"),
+            (actions.aim, "/user/hand/right/input/aim/pose"),
+        ];
+        let mut bindings = Vec::with_capacity(suggestions.len());
+        for &(action, binding) in &suggestions {
+            bindings.push(sys::ActionSuggestedBinding { action, binding: path(fns, instance, binding)? });
+        }
+        let suggested = sys::InteractionProfileSuggestedBinding {
+            ty: sys::TYPE_INTERACTION_PROFILE_SUGGESTED_BINDING,
+            next: ptr::null(),
+            interactionProfile: path(fns, instance, TOUCH_CONTROLLER)?,
+            countSuggestedBindings: bindings.len() as u32,
+            suggestedBindings: bindings.as_ptr(),
+        };
+        check("xrSuggestInteractionProfileBindings", (fns.xrSuggestInteractionProfileBindings)(instance, &suggested))?;
+
+        let attach = sys::SessionActionSetsAttachInfo {
+            ty: sys::TYPE_SESSION_ACTION_SETS_ATTACH_INFO,
+            next: ptr::null(),
+            countActionSets: 1,
+            actionSets: &actions.set,
+        };
+        check("xrAttachSessionActionSets", (fns.xrAttachSessionActionSets)(session, &attach))?;
+
+        for (space, &subaction) in actions.aim_spaces.iter_mut().zip(&hands) {
+            let info = sys::ActionSpaceCreateInfo {
+                ty: sys::TYPE_ACTION_SPACE_CREATE_INFO,
+                next: ptr::null(),
+                action: actions.aim,
+                subactionPath: subaction,
+                poseInActionSpace: to_posef(&Pose::identity()),
+            };
+            check("xrCreateActionSpace", (fns.xrCreateActionSpace)(session, &info, space))?;
+        }
+        Ok(actions)
+    }
+
+    /// Destroys the controllers' spaces. Only call this once the actions are
+    /// done with.
+    pub unsafe fn destroy_spaces(&self, fns: &Functions) {
+        for &space in &self.aim_spaces {
+            if space != sys::NULL_HANDLE {
+                (fns.xrDestroySpace)(space);
+            }
+        }
+    }
+
+    /// Reads the controllers, locating them in `space` at `time` unless that's
+    /// 0.
+    pub unsafe fn poll(&self, fns: &Functions, session: sys::Session, space: sys::Space, time: sys::Time) -> Result<InputState, Error> {
+        let active = sys::ActiveActionSet { actionSet: self.set, subactionPath: sys::NULL_PATH };
+        let sync = sys::ActionsSyncInfo {
+            ty: sys::TYPE_ACTIONS_SYNC_INFO,
+            next: ptr::null(),
+            countActiveActionSets: 1,
+            activeActionSets: &active,
+        };
+        let result = (fns.xrSyncActions)(session, &sync);
+        check("xrSyncActions", result)?;
+        let mut state = InputState::default();
+        if result == sys::SESSION_NOT_FOCUSED {
+            return Ok(state);
+        }
+        for (hand, &subaction) in self.hands.iter().enumerate() {
+            let stick = self.vector2(fns, session, self.thumbstick, subaction)?;
+            state.thumbstick[hand] = [stick.x, stick.y];
+            state.index_trigger[hand] = self.float(fns, session, self.trigger, subaction)?;
+            state.hand_trigger[hand] = self.float(fns, session, self.squeeze, subaction)?;
+            if time != 0 {
+                state.hands[hand] = self.locate(fns, self.aim_spaces[hand], space, time)?;
+            }
+        }
+        let buttons = [
+            (self.a, sys::NULL_PATH, button::A),
+            (self.b, sys::NULL_PATH, button::B),
+            (self.x, sys::NULL_PATH, button::X),
+            (self.y, sys::NULL_PATH, button::Y),
+            (self.thumbstick_click, self.hands[0], button::LTHUMB),
+            (self.thumbstick_click, self.hands[1], button::RTHUMB),
+            (self.menu, sys::NULL_PATH, button::ENTER),
+        ];
+        for &(action, subaction, bit) in &buttons {
+            if self.boolean(fns, session, action, subaction)? {
+                state.buttons |= bit;
+            }
+        }
+        let touches = [
+            (self.a_touch, sys::NULL_PATH, touch::A),
+            (self.b_touch, sys::NULL_PATH, touch::B),
+            (self.x_touch, sys::NULL_PATH, touch::X),
+            (self.y_touch, sys::NULL_PATH, touch::Y),
+            (self.thumbstick_touch, self.hands[0], touch::LTHUMB),
+            (self.thumbstick_touch, self.hands[1], touch::RTHUMB),
+            (self.trigger_touch, self.hands[0], touch::LINDEX_TRIGGER),
+            (self.trigger_touch, self.hands[1], touch::RINDEX_TRIGGER),
+            (self.thumbrest_touch, self.hands[0], touch::LTHUMB_REST),
+            (self.thumbrest_touch, self.hands[1], touch::RTHUMB_REST),
+        ];
+        for &(action, subaction, bit) in &touches {
+            if self.boolean(fns, session, action, subaction)? {
+                state.touches |= bit;
+            }
+        }
+        Ok(state)
+    }
+
+    unsafe fn locate(&self, fns: &Functions, hand: sys::Space, space: sys::Space, time: sys::Time) -> Result<Option<Pose>, Error> {
+        let mut location = sys::SpaceLocation { ty: sys::TYPE_SPACE_LOCATION, next: ptr::null_mut(), locationFlags: 0, pose: sys::Posef::default() };
+        check("xrLocateSpace", (fns.xrLocateSpace)(hand, space, time, &mut location))?;
+        Ok(if location.locationFlags & sys::SPACE_LOCATION_ORIENTATION_VALID_BIT != 0 { Some(to_pose(&location.pose)) } else { None })
+    }
+
+    fn get_info(action: sys::Action, subaction: sys::Path) -> sys::ActionStateGetInfo {
+        sys::ActionStateGetInfo {
+            ty: sys::TYPE_ACTION_STATE_GET_INFO,
+            next: ptr::null(),
+            action,
+            subactionPath: subaction,
+        }
+    }
+
+    unsafe fn boolean(&self, fns: &Functions, session: sys::Session, action: sys::Action, subaction: sys::Path) -> Result<bool, Error> {
+        let mut state = sys::ActionStateBoolean { ty: sys::TYPE_ACTION_STATE_BOOLEAN, next: ptr::null_mut(), currentState: 0, changedSinceLastSync: 0, lastChangeTime: 0, isActive: 0 };
+        check("xrGetActionStateBoolean", (fns.xrGetActionStateBoolean)(session, &Actions::get_info(action, subaction), &mut state))?;
+        Ok(state.isActive != 0 && state.currentState != 0)
+    }
+
+    unsafe fn float(&self, fns: &Functions, session: sys::Session, action: sys::Action, subaction: sys::Path) -> Result<f32, Error> {
+        let mut state = sys::ActionStateFloat { ty: sys::TYPE_ACTION_STATE_FLOAT, next: ptr::null_mut(), currentState: 0.0, changedSinceLastSync: 0, lastChangeTime: 0, isActive: 0 };
+        check("xrGetActionStateFloat", (fns.xrGetActionStateFloat)(session, &Actions::get_info(action, subaction), &mut state))?;
+        Ok(if state.isActive != 0 { state.currentState } else { 0.0 })
+    }
+
+    unsafe fn vector2(&self, fns: &Functions, session: sys::Session, action: sys::Action, subaction: sys::Path) -> Result<sys::Vector2f, Error> {
+        let mut state = sys::ActionStateVector2f { ty: sys::TYPE_ACTION_STATE_VECTOR2F, next: ptr::null_mut(), currentState: sys::Vector2f::default(), changedSinceLastSync: 0, lastChangeTime: 0, isActive: 0 };
+        check("xrGetActionStateVector2f", (fns.xrGetActionStateVector2f)(session, &Actions::get_info(action, subaction), &mut state))?;
+        Ok(if state.isActive != 0 { state.currentState } else { sys::Vector2f::default() })
+    }
+}
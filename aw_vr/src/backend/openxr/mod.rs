@@ -0,0 +1,839 @@
+//! OpenXR through `XR_KHR_opengl_enable`, sharing AW's GL context.
+//!
+//! The runtime is reached through a loader library's `xrGetInstanceProcAddr`.
+//! Anything exporting it will do, which is how the tests drive `openxr_stub`.
+
+pub mod sys;
+mod input;
+
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+use lib;
+use timing::{FramePacer, FrameStats, SystemClock};
+
+use super::{Error, Eye, EyeTextures, Fov, FrameStart, InputState, Pose, TrackingOrigin, VrBackend};
+use self::input::Actions;
+
+#[cfg(windows)]
+pub const DEFAULT_LOADER: &str = "openxr_loader";
+#[cfg(not(windows))]
+pub const DEFAULT_LOADER: &str = "libopenxr_loader.so.1";
+
+const GL_SRGB8_ALPHA8: i64 = 0x8C43;
+const GL_RGBA8: i64 = 0x8058;
+const GL_DEPTH_COMPONENT32F: i64 = 0x8CAC;
+const GL_DEPTH_COMPONENT24: i64 = 0x81A6;
+
+fn check(call: &'static str, result: sys::Result) -> Result<(), Error> {
+    if result < 0 {
+        Err(Error::Call { call, code: result })
+    } else {
+        Ok(())
+    }
+}
+
+/// Copies `name` into a fixed-size, NUL-terminated C string field.
+fn copy_name(dst: &mut [c_char], name: &str) {
+    let len = dst.len() - 1;
+    for (d, s) in dst.iter_mut().zip(name.bytes().take(len)) {
+        *d = s as c_char;
+    }
+}
+
+/// `name` must be NUL-terminated.
+unsafe fn proc_addr(get: sys::pfn::xrGetInstanceProcAddr, instance: sys::Instance, name: &'static str) -> Result<sys::VoidFunction, Error> {
+    let mut function = None;
+    let call = &name[..name.len() - 1];
+    check(call, get(instance, name.as_ptr() as *const c_char, &mut function))?;
+    function.ok_or_else(|| Error::Load(format!("runtime returned no {}", call)))
+}
+
+/// The instance extensions the runtime offers, as NUL-terminated names.
+unsafe fn instance_extensions(get: sys::pfn::xrGetInstanceProcAddr) -> Result<Vec<Vec<u8>>, Error> {
+    let enumerate = mem::transmute::<sys::VoidFunction, sys::pfn::xrEnumerateInstanceExtensionProperties>(proc_addr(get, sys::NULL_HANDLE, "xrEnumerateInstanceExtensionProperties\0")?);
+    let mut count = 0;
+    check("xrEnumerateInstanceExtensionProperties", enumerate(ptr::null(), 0, &mut count, ptr::null_mut()))?;
+    let mut properties: sys::ExtensionProperties = mem::zeroed();
+    properties.ty = sys::TYPE_EXTENSION_PROPERTIES;
+    let mut properties = vec![properties; count as usize];
+    check("xrEnumerateInstanceExtensionProperties", enumerate(ptr::null(), count, &mut count, properties.as_mut_ptr()))?;
+    Ok(properties.iter()
+        .take(count as usize)
+        .map(|property| CStr::from_ptr(property.extensionName.as_ptr()).to_bytes_with_nul().to_vec())
+        .collect())
+}
+
+macro_rules! functions {
+    ($($name:ident,)*) => {
+        #[allow(non_snake_case)]
+        struct Functions {
+            $($name: sys::pfn::$name,)*
+        }
+
+        impl Functions {
+            unsafe fn load(get: sys::pfn::xrGetInstanceProcAddr, instance: sys::Instance) -> Result<Functions, Error> {
+                Ok(Functions {
+                    $($name: mem::transmute::<sys::VoidFunction, sys::pfn::$name>(proc_addr(get, instance, concat!(stringify!($name), "\0"))?),)*
+                })
+            }
+        }
+    }
+}
+
+functions! {
+    xrDestroyInstance,
+    xrPollEvent,
+    xrStringToPath,
+    xrGetSystem,
+    xrEnumerateViewConfigurationViews,
+    xrGetOpenGLGraphicsRequirementsKHR,
+    xrCreateSession,
+    xrDestroySession,
+    xrBeginSession,
+    xrEndSession,
+    xrCreateReferenceSpace,
+    xrCreateActionSpace,
+    xrDestroySpace,
+    xrLocateSpace,
+    xrEnumerateSwapchainFormats,
+    xrCreateSwapchain,
+    xrDestroySwapchain,
+    xrEnumerateSwapchainImages,
+    xrAcquireSwapchainImage,
+    xrWaitSwapchainImage,
+    xrReleaseSwapchainImage,
+    xrWaitFrame,
+    xrBeginFrame,
+    xrEndFrame,
+    xrLocateViews,
+    xrCreateActionSet,
+    xrCreateAction,
+    xrSuggestInteractionProfileBindings,
+    xrAttachSessionActionSets,
+    xrSyncActions,
+    xrGetActionStateBoolean,
+    xrGetActionStateFloat,
+    xrGetActionStateVector2f,
+}
+
+fn session_state_name(state: i32) -> &'static str {
+    match state {
+        sys::SESSION_STATE_IDLE => "idle",
+        sys::SESSION_STATE_READY => "ready",
+        sys::SESSION_STATE_SYNCHRONIZED => "synchronized",
+        sys::SESSION_STATE_VISIBLE => "visible",
+        sys::SESSION_STATE_FOCUSED => "focused",
+        sys::SESSION_STATE_STOPPING => "stopping",
+        sys::SESSION_STATE_LOSS_PENDING => "loss pending",
+        sys::SESSION_STATE_EXITING => "exiting",
+        _ => "unknown",
+    }
+}
+
+fn to_pose(pose: &sys::Posef) -> Pose {
+    Pose {
+        position: [pose.position.x, pose.position.y, pose.position.z],
+        orientation: [pose.orientation.x, pose.orientation.y, pose.orientation.z, pose.orientation.w],
+    }
+}
+
+fn to_posef(pose: &Pose) -> sys::Posef {
+    let [x, y, z, w] = pose.orientation;
+    sys::Posef {
+        orientation: sys::Quaternionf { x, y, z, w },
+        position: sys::Vector3f { x: pose.position[0], y: pose.position[1], z: pose.position[2] },
+    }
+}
+
+fn to_fov(fov: &sys::Fovf) -> Fov {
+    Fov {
+        up: fov.angleUp.tan(),
+        down: (-fov.angleDown).tan(),
+        left: (-fov.angleLeft).tan(),
+        right: fov.angleRight.tan(),
+    }
+}
+
+fn to_fovf(fov: &Fov) -> sys::Fovf {
+    sys::Fovf {
+        angleLeft: -fov.left.atan(),
+        angleRight: fov.right.atan(),
+        angleUp: fov.up.atan(),
+        angleDown: -fov.down.atan(),
+    }
+}
+
+struct SwapChain {
+    handle: sys::Swapchain,
+    images: Vec<u32>,
+    width: u32,
+    height: u32,
+    /// Whether an image is acquired and not yet released.
+    acquired: bool,
+}
+
+impl SwapChain {
+    /// Acquires the next image and waits until it can be drawn to.
+    fn acquire(&mut self, fns: &Functions) -> Result<u32, Error> {
+        self.release(fns)?;
+        let acquire = sys::SwapchainImageAcquireInfo { ty: sys::TYPE_SWAPCHAIN_IMAGE_ACQUIRE_INFO, next: ptr::null() };
+        let wait = sys::SwapchainImageWaitInfo { ty: sys::TYPE_SWAPCHAIN_IMAGE_WAIT_INFO, next: ptr::null(), timeout: sys::INFINITE_DURATION };
+        let mut index = 0;
+        unsafe {
+            check("xrAcquireSwapchainImage", (fns.xrAcquireSwapchainImage)(self.handle, &acquire, &mut index))?;
+            self.acquired = true;
+            check("xrWaitSwapchainImage", (fns.xrWaitSwapchainImage)(self.handle, &wait))?;
+        }
+        self.images.get(index as usize)
+            .cloned()
+            .ok_or(Error::Call { call: "xrAcquireSwapchainImage", code: sys::ERROR_RUNTIME_FAILURE })
+    }
+
+    fn release(&mut self, fns: &Functions) -> Result<(), Error> {
+        if !self.acquired {
+            return Ok(());
+        }
+        self.acquired = false;
+        let info = sys::SwapchainImageReleaseInfo { ty: sys::TYPE_SWAPCHAIN_IMAGE_RELEASE_INFO, next: ptr::null() };
+        unsafe {
+            check("xrReleaseSwapchainImage", (fns.xrReleaseSwapchainImage)(self.handle, &info))
+        }
+    }
+
+    fn sub_image(&self) -> sys::SwapchainSubImage {
+        sys::SwapchainSubImage {
+            swapchain: self.handle,
+            imageRect: sys::Rect2Di {
+                offset: sys::Offset2Di { x: 0, y: 0 },
+                extent: sys::Extent2Di { width: self.width as i32, height: self.height as i32 },
+            },
+            imageArrayIndex: 0,
+        }
+    }
+}
+
+pub struct OpenXrBackend {
+    fns: Functions,
+    instance: sys::Instance,
+    system: sys::SystemId,
+    session: sys::Session,
+    /// Head-locked space, used to find the head when recentering.
+    view_space: sys::Space,
+    /// The stage for the floor origin, or local space for the eye origin.
+    space_type: i32,
+    /// `space_type` offset by `origin`; everything is rendered and submitted in it.
+    space: sys::Space,
+    origin: Pose,
+    actions: Option<Actions>,
+    running: bool,
+    /// Whether the runtime moved a reference space, as recentering does,
+    /// since the last frame began.
+    recentered: bool,
+    frame: Option<sys::FrameState>,
+    display_time: sys::Time,
+    views: [sys::View; 2],
+    swap_chains: Vec<SwapChain>,
+    /// Empty unless `XR_KHR_composition_layer_depth` is enabled.
+    depth_swap_chains: Vec<SwapChain>,
+    depth_supported: bool,
+    /// Near and far clip distances in meters, if known.
+    depth_range: Option<(f32, f32)>,
+    committed: [bool; 2],
+    /// On the system clock, since OpenXR's own time can't be read without
+    /// another extension. That leaves latency unmeasured.
+    pacer: FramePacer,
+    _library: lib::Library,
+}
+
+// The raw pointers in `frame` and `views` are only ever null `next` chains.
+unsafe impl Send for OpenXrBackend {}
+
+impl OpenXrBackend {
+    /// Loads the runtime through `loader` and creates a session on the GL
+    /// context given by the Win32 `dc` and `glrc` handles, tracking from
+    /// `origin`.
+    pub fn new(loader: &str, origin: TrackingOrigin, dc: *mut c_void, glrc: *mut c_void) -> Result<OpenXrBackend, Error> {
+        let library = lib::Library::new(loader).map_err(|error| Error::Load(format!("{}: {}", loader, error)))?;
+        unsafe {
+            let get = *library.get::<sys::pfn::xrGetInstanceProcAddr>(b"xrGetInstanceProcAddr\0")
+                .map_err(|error| Error::Load(format!("{}: {}", loader, error)))?;
+            let create_instance = mem::transmute::<sys::VoidFunction, sys::pfn::xrCreateInstance>(proc_addr(get, sys::NULL_HANDLE, "xrCreateInstance\0")?);
+            let depth_supported = instance_extensions(get)?.iter().any(|name| &name[..] == sys::KHR_COMPOSITION_LAYER_DEPTH_EXTENSION_NAME);
+            let mut extensions = vec![sys::KHR_OPENGL_ENABLE_EXTENSION_NAME.as_ptr() as *const c_char];
+            if depth_supported {
+                extensions.push(sys::KHR_COMPOSITION_LAYER_DEPTH_EXTENSION_NAME.as_ptr() as *const c_char);
+            }
+            let mut info = sys::InstanceCreateInfo {
+                ty: sys::TYPE_INSTANCE_CREATE_INFO,
+                next: ptr::null(),
+                createFlags: 0,
+                applicationInfo: mem::zeroed(),
+                enabledApiLayerCount: 0,
+                enabledApiLayerNames: ptr::null(),
+                enabledExtensionCount: extensions.len() as u32,
+                enabledExtensionNames: extensions.as_ptr(),
+            };
+            copy_name(&mut info.applicationInfo.applicationName, "AW_VR");
+            copy_name(&mut info.applicationInfo.engineName, "RenderWare");
+            info.applicationInfo.apiVersion = sys::CURRENT_API_VERSION;
+            let mut instance = sys::NULL_HANDLE;
+            check("xrCreateInstance", create_instance(&info, &mut instance))?;
+            let fns = match Functions::load(get, instance) {
+                Ok(fns) => fns,
+                Err(error) => {
+                    if let Ok(destroy) = proc_addr(get, instance, "xrDestroyInstance\0") {
+                        mem::transmute::<sys::VoidFunction, sys::pfn::xrDestroyInstance>(destroy)(instance);
+                    }
+                    return Err(error);
+                },
+            };
+            let mut backend = OpenXrBackend {
+                fns,
+                instance,
+                system: 0,
+                session: sys::NULL_HANDLE,
+                view_space: sys::NULL_HANDLE,
+                space_type: match origin {
+                    TrackingOrigin::Floor => sys::REFERENCE_SPACE_TYPE_STAGE,
+                    TrackingOrigin::Eye => sys::REFERENCE_SPACE_TYPE_LOCAL,
+                },
+                space: sys::NULL_HANDLE,
+                origin: Pose::identity(),
+                actions: None,
+                running: false,
+                recentered: false,
+                frame: None,
+                display_time: 0,
+                views: [sys::View { fov: to_fovf(&Fov::square()), .. mem::zeroed() }; 2],
+                swap_chains: Vec::new(),
+                depth_swap_chains: Vec::new(),
+                depth_supported,
+                depth_range: None,
+                committed: [false; 2],
+                pacer: FramePacer::new(Box::new(SystemClock::new())),
+                _library: library,
+            };
+            backend.create_session(dc, glrc)?;
+            Ok(backend)
+        }
+    }
+
+    unsafe fn create_session(&mut self, dc: *mut c_void, glrc: *mut c_void) -> Result<(), Error> {
+        let system_info = sys::SystemGetInfo {
+            ty: sys::TYPE_SYSTEM_GET_INFO,
+            next: ptr::null(),
+            formFactor: sys::FORM_FACTOR_HEAD_MOUNTED_DISPLAY,
+        };
+        check("xrGetSystem", (self.fns.xrGetSystem)(self.instance, &system_info, &mut self.system))?;
+        // XR_KHR_opengl_enable requires this before xrCreateSession, whatever the answer.
+        let mut requirements = sys::GraphicsRequirementsOpenGLKHR {
+            ty: sys::TYPE_GRAPHICS_REQUIREMENTS_OPENGL_KHR,
+            next: ptr::null_mut(),
+            minApiVersionSupported: 0,
+            maxApiVersionSupported: 0,
+        };
+        check("xrGetOpenGLGraphicsRequirementsKHR", (self.fns.xrGetOpenGLGraphicsRequirementsKHR)(self.instance, self.system, &mut requirements))?;
+        let binding = sys::GraphicsBindingOpenGLWin32KHR {
+            ty: sys::TYPE_GRAPHICS_BINDING_OPENGL_WIN32_KHR,
+            next: ptr::null(),
+            hDC: dc,
+            hGLRC: glrc,
+        };
+        let info = sys::SessionCreateInfo {
+            ty: sys::TYPE_SESSION_CREATE_INFO,
+            next: &binding as *const _ as *const c_void,
+            createFlags: 0,
+            systemId: self.system,
+        };
+        check("xrCreateSession", (self.fns.xrCreateSession)(self.instance, &info, &mut self.session))?;
+        self.view_space = self.reference_space(sys::REFERENCE_SPACE_TYPE_VIEW, &Pose::identity())?;
+        self.space = self.reference_space(self.space_type, &Pose::identity())?;
+        self.actions = Some(Actions::new(&self.fns, self.instance, self.session)?);
+        Ok(())
+    }
+
+    fn reference_space(&self, kind: i32, pose: &Pose) -> Result<sys::Space, Error> {
+        let info = sys::ReferenceSpaceCreateInfo {
+            ty: sys::TYPE_REFERENCE_SPACE_CREATE_INFO,
+            next: ptr::null(),
+            referenceSpaceType: kind,
+            poseInReferenceSpace: to_posef(pose),
+        };
+        let mut space = sys::NULL_HANDLE;
+        unsafe {
+            check("xrCreateReferenceSpace", (self.fns.xrCreateReferenceSpace)(self.session, &info, &mut space))?;
+        }
+        Ok(space)
+    }
+
+    fn poll_events(&mut self) -> Result<(), Error> {
+        loop {
+            let mut event: sys::EventDataBuffer = unsafe { mem::zeroed() };
+            event.ty = sys::TYPE_EVENT_DATA_BUFFER;
+            let result = unsafe { (self.fns.xrPollEvent)(self.instance, &mut event) };
+            check("xrPollEvent", result)?;
+            if result == sys::EVENT_UNAVAILABLE {
+                return Ok(());
+            }
+            match event.ty {
+                sys::TYPE_EVENT_DATA_SESSION_STATE_CHANGED => {
+                    let changed = unsafe { &*(&event as *const _ as *const sys::EventDataSessionStateChanged) };
+                    self.session_state_changed(changed.state)?;
+                },
+                sys::TYPE_EVENT_DATA_REFERENCE_SPACE_CHANGE_PENDING => self.recentered = true,
+                sys::TYPE_EVENT_DATA_INSTANCE_LOSS_PENDING => {
+                    self.running = false;
+                    return Err(Error::Call { call: "xrPollEvent", code: sys::ERROR_INSTANCE_LOST });
+                },
+                _ => {},
+            }
+        }
+    }
+
+    fn session_state_changed(&mut self, state: i32) -> Result<(), Error> {
+        info!("session state: {}", session_state_name(state));
+        match state {
+            sys::SESSION_STATE_READY => {
+                let info = sys::SessionBeginInfo {
+                    ty: sys::TYPE_SESSION_BEGIN_INFO,
+                    next: ptr::null(),
+                    primaryViewConfigurationType: sys::VIEW_CONFIGURATION_TYPE_PRIMARY_STEREO,
+                };
+                unsafe {
+                    check("xrBeginSession", (self.fns.xrBeginSession)(self.session, &info))?;
+                }
+                self.running = true;
+            },
+            sys::SESSION_STATE_STOPPING => {
+                self.running = false;
+                self.frame = None;
+                unsafe {
+                    check("xrEndSession", (self.fns.xrEndSession)(self.session))?;
+                }
+            },
+            sys::SESSION_STATE_LOSS_PENDING => {
+                self.running = false;
+                self.frame = None;
+                return Err(Error::Call { call: "xrPollEvent", code: sys::ERROR_SESSION_LOST });
+            },
+            sys::SESSION_STATE_EXITING => {
+                self.running = false;
+                self.frame = None;
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn swap_chain_formats(&self) -> Result<Vec<i64>, Error> {
+        let mut count = 0;
+        unsafe {
+            check("xrEnumerateSwapchainFormats", (self.fns.xrEnumerateSwapchainFormats)(self.session, 0, &mut count, ptr::null_mut()))?;
+            let mut formats = vec![0; count as usize];
+            check("xrEnumerateSwapchainFormats", (self.fns.xrEnumerateSwapchainFormats)(self.session, count, &mut count, formats.as_mut_ptr()))?;
+            formats.truncate(count as usize);
+            Ok(formats)
+        }
+    }
+
+    fn swap_chain(&self, format: i64, usage: u64, width: u32, height: u32) -> Result<SwapChain, Error> {
+        let info = sys::SwapchainCreateInfo {
+            ty: sys::TYPE_SWAPCHAIN_CREATE_INFO,
+            next: ptr::null(),
+            createFlags: 0,
+            usageFlags: usage,
+            format,
+            sampleCount: 1,
+            width,
+            height,
+            faceCount: 1,
+            arraySize: 1,
+            mipCount: 1,
+        };
+        unsafe {
+            let mut handle = sys::NULL_HANDLE;
+            check("xrCreateSwapchain", (self.fns.xrCreateSwapchain)(self.session, &info, &mut handle))?;
+            let mut swap_chain = SwapChain { handle, images: Vec::new(), width, height, acquired: false };
+            let mut count = 0;
+            check("xrEnumerateSwapchainImages", (self.fns.xrEnumerateSwapchainImages)(handle, 0, &mut count, ptr::null_mut()))
+                .inspect_err(|_| { (self.fns.xrDestroySwapchain)(handle); })?;
+            let mut images = vec![sys::SwapchainImageOpenGLKHR { ty: sys::TYPE_SWAPCHAIN_IMAGE_OPENGL_KHR, next: ptr::null_mut(), image: 0 }; count as usize];
+            check("xrEnumerateSwapchainImages", (self.fns.xrEnumerateSwapchainImages)(handle, count, &mut count, images.as_mut_ptr()))
+                .inspect_err(|_| { (self.fns.xrDestroySwapchain)(handle); })?;
+            swap_chain.images = images.iter().take(count as usize).map(|image| image.image).collect();
+            Ok(swap_chain)
+        }
+    }
+
+    fn destroy_swap_chains(&mut self) {
+        for swap_chain in self.swap_chains.drain(..).chain(self.depth_swap_chains.drain(..)) {
+            unsafe {
+                (self.fns.xrDestroySwapchain)(swap_chain.handle);
+            }
+        }
+        self.committed = [false; 2];
+    }
+
+    /// Makes a depth swap chain per eye if the runtime takes depth. Depth only
+    /// improves reprojection, so failing to get it isn't an error.
+    fn create_depth_swap_chains(&mut self, formats: &[i64], width: u32, height: u32) {
+        let format = match [GL_DEPTH_COMPONENT32F, GL_DEPTH_COMPONENT24].iter().find(|format| formats.contains(format)) {
+            Some(&format) if self.depth_supported => format,
+            _ => return,
+        };
+        let usage = sys::SWAPCHAIN_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT | sys::SWAPCHAIN_USAGE_TRANSFER_DST_BIT;
+        for _ in 0..2 {
+            match self.swap_chain(format, usage, width, height) {
+                Ok(swap_chain) => self.depth_swap_chains.push(swap_chain),
+                Err(error) => {
+                    warn!("no depth swap chains, so no positional reprojection: {}", error);
+                    for swap_chain in self.depth_swap_chains.drain(..) {
+                        unsafe {
+                            (self.fns.xrDestroySwapchain)(swap_chain.handle);
+                        }
+                    }
+                    return;
+                },
+            }
+        }
+    }
+
+    /// Releases whatever images the eye has acquired.
+    fn release(&mut self, eye: usize) -> Result<(), Error> {
+        let fns = &self.fns;
+        for swap_chain in self.swap_chains.get_mut(eye).into_iter().chain(self.depth_swap_chains.get_mut(eye)) {
+            swap_chain.release(fns)?;
+        }
+        Ok(())
+    }
+}
+
+impl VrBackend for OpenXrBackend {
+    fn name(&self) -> &'static str {
+        "OpenXR"
+    }
+
+    fn begin_frame(&mut self) -> Result<FrameStart, Error> {
+        self.poll_events()?;
+        let recentered = mem::replace(&mut self.recentered, false);
+        if !self.running {
+            return Ok(FrameStart { render: false, recentered });
+        }
+        let wait = sys::FrameWaitInfo { ty: sys::TYPE_FRAME_WAIT_INFO, next: ptr::null() };
+        let begin = sys::FrameBeginInfo { ty: sys::TYPE_FRAME_BEGIN_INFO, next: ptr::null() };
+        unsafe {
+            let mut state: sys::FrameState = mem::zeroed();
+            state.ty = sys::TYPE_FRAME_STATE;
+            check("xrWaitFrame", (self.fns.xrWaitFrame)(self.session, &wait, &mut state))?;
+            check("xrBeginFrame", (self.fns.xrBeginFrame)(self.session, &begin))?;
+            self.frame = Some(state);
+            self.pacer.begin_frame();
+            self.display_time = state.predictedDisplayTime;
+            self.committed = [false; 2];
+            Ok(FrameStart { render: state.shouldRender != 0, recentered })
+        }
+    }
+
+    fn eye_poses(&mut self) -> Result<[Pose; 2], Error> {
+        if self.display_time == 0 {
+            return Ok([Pose::identity(), Pose::identity()]);
+        }
+        let info = sys::ViewLocateInfo {
+            ty: sys::TYPE_VIEW_LOCATE_INFO,
+            next: ptr::null(),
+            viewConfigurationType: sys::VIEW_CONFIGURATION_TYPE_PRIMARY_STEREO,
+            displayTime: self.display_time,
+            space: self.space,
+        };
+        unsafe {
+            let mut state = sys::ViewState { ty: sys::TYPE_VIEW_STATE, next: ptr::null_mut(), viewStateFlags: 0 };
+            let mut views: [sys::View; 2] = mem::zeroed();
+            views[0].ty = sys::TYPE_VIEW;
+            views[1].ty = sys::TYPE_VIEW;
+            let mut count = 0;
+            check("xrLocateViews", (self.fns.xrLocateViews)(self.session, &info, &mut state, 2, &mut count, views.as_mut_ptr()))?;
+            self.views = views;
+        }
+        Ok([to_pose(&self.views[0].pose), to_pose(&self.views[1].pose)])
+    }
+
+    /// The FOV `xrLocateViews` last reported, which is also what gets submitted.
+    fn eye_fov(&mut self, eye: Eye) -> Result<Fov, Error> {
+        Ok(to_fov(&self.views[eye.index()].fov))
+    }
+
+    fn recommended_size(&mut self, pixel_density: f32) -> Result<(u32, u32), Error> {
+        unsafe {
+            let mut views: [sys::ViewConfigurationView; 2] = mem::zeroed();
+            views[0].ty = sys::TYPE_VIEW_CONFIGURATION_VIEW;
+            views[1].ty = sys::TYPE_VIEW_CONFIGURATION_VIEW;
+            let mut count = 0;
+            check("xrEnumerateViewConfigurationViews", (self.fns.xrEnumerateViewConfigurationViews)(self.instance, self.system, sys::VIEW_CONFIGURATION_TYPE_PRIMARY_STEREO, 2, &mut count, views.as_mut_ptr()))?;
+            let scale = |recommended: u32, max: u32| ((recommended as f32 * pixel_density).round() as u32).min(max).max(1);
+            Ok((
+                views.iter().map(|view| scale(view.recommendedImageRectWidth, view.maxImageRectWidth)).max().unwrap_or(1),
+                views.iter().map(|view| scale(view.recommendedImageRectHeight, view.maxImageRectHeight)).max().unwrap_or(1),
+            ))
+        }
+    }
+
+    fn create_swap_chains(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        self.destroy_swap_chains();
+        let formats = self.swap_chain_formats()?;
+        let format = [GL_SRGB8_ALPHA8, GL_RGBA8].iter()
+            .find(|format| formats.contains(format))
+            .or_else(|| formats.first())
+            .cloned()
+            .ok_or(Error::Call { call: "xrEnumerateSwapchainFormats", code: sys::ERROR_RUNTIME_FAILURE })?;
+        let usage = sys::SWAPCHAIN_USAGE_COLOR_ATTACHMENT_BIT | sys::SWAPCHAIN_USAGE_TRANSFER_DST_BIT | sys::SWAPCHAIN_USAGE_SAMPLED_BIT;
+        for _ in 0..2 {
+            let swap_chain = self.swap_chain(format, usage, width, height)?;
+            self.swap_chains.push(swap_chain);
+        }
+        self.create_depth_swap_chains(&formats, width, height);
+        Ok(())
+    }
+
+    fn acquire_eye_textures(&mut self, eye: Eye) -> Result<EyeTextures, Error> {
+        let eye = eye.index();
+        if self.swap_chains.len() != 2 {
+            return Err(Error::Call { call: "xrAcquireSwapchainImage", code: sys::ERROR_CALL_ORDER_INVALID });
+        }
+        let fns = &self.fns;
+        Ok(EyeTextures {
+            color: self.swap_chains[eye].acquire(fns)?,
+            depth: match self.depth_swap_chains.get_mut(eye) {
+                Some(swap_chain) => Some(swap_chain.acquire(fns)?),
+                None => None,
+            },
+        })
+    }
+
+    fn commit(&mut self, eye: Eye) -> Result<(), Error> {
+        let eye = eye.index();
+        if self.swap_chains.get(eye).is_some_and(|swap_chain| swap_chain.acquired) {
+            self.release(eye)?;
+            self.committed[eye] = true;
+        }
+        Ok(())
+    }
+
+    fn set_depth_range(&mut self, range: Option<(f32, f32)>) {
+        self.depth_range = range;
+    }
+
+    fn submit(&mut self) -> Result<(), Error> {
+        let state = match self.frame.take() {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+        for eye in 0..2 {
+            self.release(eye)?;
+        }
+        let depth: Vec<sys::CompositionLayerDepthInfoKHR> = match self.depth_range {
+            Some((near, far)) => self.depth_swap_chains.iter().map(|swap_chain| {
+                sys::CompositionLayerDepthInfoKHR {
+                    ty: sys::TYPE_COMPOSITION_LAYER_DEPTH_INFO_KHR,
+                    next: ptr::null(),
+                    subImage: swap_chain.sub_image(),
+                    minDepth: 0.0,
+                    maxDepth: 1.0,
+                    nearZ: near,
+                    farZ: far,
+                }
+            }).collect(),
+            None => Vec::new(),
+        };
+        let views: Vec<sys::CompositionLayerProjectionView> = self.swap_chains.iter().zip(&self.views).enumerate().map(|(eye, (swap_chain, view))| {
+            sys::CompositionLayerProjectionView {
+                ty: sys::TYPE_COMPOSITION_LAYER_PROJECTION_VIEW,
+                next: depth.get(eye).map_or(ptr::null(), |depth| depth as *const _ as *const c_void),
+                pose: view.pose,
+                fov: view.fov,
+                subImage: swap_chain.sub_image(),
+            }
+        }).collect();
+        let layer = sys::CompositionLayerProjection {
+            ty: sys::TYPE_COMPOSITION_LAYER_PROJECTION,
+            next: ptr::null(),
+            layerFlags: 0,
+            space: self.space,
+            viewCount: views.len() as u32,
+            views: views.as_ptr(),
+        };
+        let layers = [&layer as *const _ as *const sys::CompositionLayerBaseHeader];
+        let complete = state.shouldRender != 0 && views.len() == 2 && self.committed == [true, true];
+        let info = sys::FrameEndInfo {
+            ty: sys::TYPE_FRAME_END_INFO,
+            next: ptr::null(),
+            displayTime: state.predictedDisplayTime,
+            environmentBlendMode: sys::ENVIRONMENT_BLEND_MODE_OPAQUE,
+            layerCount: if complete { 1 } else { 0 },
+            layers: layers.as_ptr(),
+        };
+        unsafe {
+            check("xrEndFrame", (self.fns.xrEndFrame)(self.session, &info))?;
+        }
+        self.pacer.end_frame();
+        Ok(())
+    }
+
+    fn take_frame_stats(&mut self) -> FrameStats {
+        self.pacer.take_stats()
+    }
+
+    fn poll_input(&mut self) -> Result<InputState, Error> {
+        if !self.running {
+            return Ok(InputState::default());
+        }
+        match self.actions {
+            Some(ref actions) => unsafe { actions.poll(&self.fns, self.session, self.space, self.display_time) },
+            None => Ok(InputState::default()),
+        }
+    }
+
+    fn recenter(&mut self) -> Result<(), Error> {
+        if self.display_time == 0 {
+            return Ok(());
+        }
+        let mut location = sys::SpaceLocation {
+            ty: sys::TYPE_SPACE_LOCATION,
+            next: ptr::null_mut(),
+            locationFlags: 0,
+            pose: sys::Posef::default(),
+        };
+        unsafe {
+            check("xrLocateSpace", (self.fns.xrLocateSpace)(self.view_space, self.space, self.display_time, &mut location))?;
+        }
+        let valid = sys::SPACE_LOCATION_ORIENTATION_VALID_BIT | sys::SPACE_LOCATION_POSITION_VALID_BIT;
+        if location.locationFlags & valid != valid {
+            return Ok(());
+        }
+        // Like LibOVR's recenter: heading and position, and height too unless
+        // the origin is on the floor.
+        let head = to_pose(&location.pose);
+        let height = if self.space_type == sys::REFERENCE_SPACE_TYPE_STAGE { 0.0 } else { head.position[1] };
+        let offset = Pose::from_yaw(head.yaw(), [head.position[0], height, head.position[2]]);
+        let origin = self.origin.compose(&offset);
+        let space = self.reference_space(self.space_type, &origin)?;
+        unsafe {
+            (self.fns.xrDestroySpace)(self.space);
+        }
+        self.space = space;
+        self.origin = origin;
+        Ok(())
+    }
+}
+
+impl Drop for OpenXrBackend {
+    fn drop(&mut self) {
+        self.destroy_swap_chains();
+        unsafe {
+            if let Some(ref actions) = self.actions {
+                actions.destroy_spaces(&self.fns);
+            }
+            for space in &[self.space, self.view_space] {
+                if *space != sys::NULL_HANDLE {
+                    (self.fns.xrDestroySpace)(*space);
+                }
+            }
+            if self.session != sys::NULL_HANDLE {
+                (self.fns.xrDestroySession)(self.session);
+            }
+            (self.fns.xrDestroyInstance)(self.instance);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::ptr;
+
+    use lib;
+
+    use super::*;
+    use backend::button;
+
+    /// Builds `openxr_stub` and returns the path of its shared library.
+    fn stub_runtime() -> PathBuf {
+        let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let target_dir = manifest_dir.join("target").join("openxr_stub");
+        let status = Command::new(env!("CARGO"))
+            .arg("build")
+            .arg("--quiet")
+            .arg("--manifest-path")
+            .arg(manifest_dir.join("..").join("openxr_stub").join("Cargo.toml"))
+            .env("CARGO_TARGET_DIR", &target_dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        target_dir.join("debug").join(format!("{}openxr_stub{}", env::consts::DLL_PREFIX, env::consts::DLL_SUFFIX))
+    }
+
+    fn stub_counter(path: &PathBuf, name: &[u8]) -> u64 {
+        let library = lib::Library::new(path).unwrap();
+        unsafe {
+            let counter = library.get::<extern "system" fn() -> u64>(name).unwrap();
+            counter()
+        }
+    }
+
+    #[test]
+    fn missing_runtime_is_a_load_error() {
+        match OpenXrBackend::new("aw_vr_no_such_openxr_runtime", TrackingOrigin::Floor, ptr::null_mut(), ptr::null_mut()) {
+            Err(Error::Load(_)) => {},
+            Err(error) => panic!("unexpected error {}", error),
+            Ok(_) => panic!("loaded a runtime that doesn't exist"),
+        }
+    }
+
+    #[test]
+    fn renders_stereo_frames_on_stub_runtime() {
+        let path = stub_runtime();
+        let mut backend = OpenXrBackend::new(path.to_str().unwrap(), TrackingOrigin::Floor, ptr::null_mut(), ptr::null_mut()).unwrap();
+        let ended = stub_counter(&path, b"xrStubEndedFrames\0");
+        let layers = stub_counter(&path, b"xrStubSubmittedLayers\0");
+        let depth_views = stub_counter(&path, b"xrStubDepthViews\0");
+
+        assert!(backend.begin_frame().unwrap().render);
+        let poses = backend.eye_poses().unwrap();
+        assert!(poses[0].position[0] < poses[1].position[0]);
+        // The stub's views are asymmetric; they should come back as tangents.
+        let fov = backend.eye_fov(Eye::Left).unwrap();
+        assert!((fov.left - 0.8f32.tan()).abs() < 1e-6 && (fov.down - 0.85f32.tan()).abs() < 1e-6);
+        assert_eq!(backend.recommended_size(1.0).unwrap(), (1440, 1600));
+        assert_eq!(backend.recommended_size(1.5).unwrap(), (2160, 2400));
+        assert_eq!(backend.recommended_size(4.0).unwrap(), (4096, 4096));
+        backend.create_swap_chains(64, 32).unwrap();
+        backend.set_depth_range(Some((0.1, 1000.0)));
+        for &eye in &[Eye::Left, Eye::Right] {
+            let textures = backend.acquire_eye_textures(eye).unwrap();
+            assert_ne!(textures.color, 0);
+            assert!(textures.depth.is_some());
+            backend.commit(eye).unwrap();
+        }
+        backend.submit().unwrap();
+        assert_eq!(stub_counter(&path, b"xrStubEndedFrames\0"), ended + 1);
+        assert_eq!(stub_counter(&path, b"xrStubSubmittedLayers\0"), layers + 1);
+        assert_eq!(stub_counter(&path, b"xrStubDepthViews\0"), depth_views + 2);
+
+        // A frame with an eye missing is ended without a layer.
+        assert!(backend.begin_frame().unwrap().render);
+        backend.acquire_eye_textures(Eye::Left).unwrap();
+        backend.commit(Eye::Left).unwrap();
+        backend.submit().unwrap();
+        assert_eq!(stub_counter(&path, b"xrStubEndedFrames\0"), ended + 2);
+        assert_eq!(stub_counter(&path, b"xrStubSubmittedLayers\0"), layers + 1);
+
+        let input = backend.poll_input().unwrap();
+        assert_eq!(input.buttons & button::ENTER, 0);
+        assert!(input.hands.iter().all(Option::is_some));
+        backend.recenter().unwrap();
+    }
+}
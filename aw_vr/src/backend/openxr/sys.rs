@@ -0,0 +1,597 @@
+//! The subset of the OpenXR 1.0 C API used by AW_VR.
+//!
+//! Handles are declared as `u64` everywhere: that is their definition on 32-bit
+//! targets, and on 64-bit targets they are pointers of the same size and
+//! calling convention.
+
+#![allow(dead_code, non_camel_case_types, non_snake_case)]
+
+use std::os::raw::{c_char, c_void};
+
+pub type Result = i32;
+pub type StructureType = i32;
+pub type Instance = u64;
+pub type Session = u64;
+pub type Space = u64;
+pub type Swapchain = u64;
+pub type ActionSet = u64;
+pub type Action = u64;
+pub type SystemId = u64;
+pub type Path = u64;
+pub type Time = i64;
+pub type Duration = i64;
+pub type Bool32 = u32;
+pub type Version = u64;
+
+pub const NULL_HANDLE: u64 = 0;
+pub const NULL_PATH: Path = 0;
+pub const INFINITE_DURATION: Duration = 0x7fff_ffff_ffff_ffff;
+pub const CURRENT_API_VERSION: Version = 1 << 48;
+
+pub const SUCCESS: Result = 0;
+pub const TIMEOUT_EXPIRED: Result = 1;
+pub const SESSION_LOSS_PENDING: Result = 3;
+pub const EVENT_UNAVAILABLE: Result = 4;
+pub const SESSION_NOT_FOCUSED: Result = 8;
+pub const FRAME_DISCARDED: Result = 9;
+pub const ERROR_VALIDATION_FAILURE: Result = -1;
+pub const ERROR_RUNTIME_FAILURE: Result = -2;
+pub const ERROR_FUNCTION_UNSUPPORTED: Result = -7;
+pub const ERROR_HANDLE_INVALID: Result = -12;
+pub const ERROR_INSTANCE_LOST: Result = -13;
+pub const ERROR_SESSION_NOT_RUNNING: Result = -16;
+pub const ERROR_SESSION_LOST: Result = -17;
+pub const ERROR_CALL_ORDER_INVALID: Result = -37;
+
+pub const TYPE_EXTENSION_PROPERTIES: StructureType = 2;
+pub const TYPE_INSTANCE_CREATE_INFO: StructureType = 3;
+pub const TYPE_SYSTEM_GET_INFO: StructureType = 4;
+pub const TYPE_VIEW_LOCATE_INFO: StructureType = 6;
+pub const TYPE_VIEW: StructureType = 7;
+pub const TYPE_SESSION_CREATE_INFO: StructureType = 8;
+pub const TYPE_SWAPCHAIN_CREATE_INFO: StructureType = 9;
+pub const TYPE_SESSION_BEGIN_INFO: StructureType = 10;
+pub const TYPE_VIEW_STATE: StructureType = 11;
+pub const TYPE_FRAME_END_INFO: StructureType = 12;
+pub const TYPE_EVENT_DATA_BUFFER: StructureType = 16;
+pub const TYPE_EVENT_DATA_INSTANCE_LOSS_PENDING: StructureType = 17;
+pub const TYPE_EVENT_DATA_SESSION_STATE_CHANGED: StructureType = 18;
+pub const TYPE_ACTION_STATE_BOOLEAN: StructureType = 23;
+pub const TYPE_ACTION_STATE_FLOAT: StructureType = 24;
+pub const TYPE_ACTION_STATE_VECTOR2F: StructureType = 25;
+pub const TYPE_ACTION_SET_CREATE_INFO: StructureType = 28;
+pub const TYPE_ACTION_CREATE_INFO: StructureType = 29;
+pub const TYPE_FRAME_WAIT_INFO: StructureType = 33;
+pub const TYPE_COMPOSITION_LAYER_PROJECTION: StructureType = 35;
+pub const TYPE_REFERENCE_SPACE_CREATE_INFO: StructureType = 37;
+pub const TYPE_ACTION_SPACE_CREATE_INFO: StructureType = 38;
+pub const TYPE_EVENT_DATA_REFERENCE_SPACE_CHANGE_PENDING: StructureType = 40;
+pub const TYPE_VIEW_CONFIGURATION_VIEW: StructureType = 41;
+pub const TYPE_SPACE_LOCATION: StructureType = 42;
+pub const TYPE_FRAME_STATE: StructureType = 44;
+pub const TYPE_FRAME_BEGIN_INFO: StructureType = 46;
+pub const TYPE_COMPOSITION_LAYER_PROJECTION_VIEW: StructureType = 48;
+pub const TYPE_INTERACTION_PROFILE_SUGGESTED_BINDING: StructureType = 51;
+pub const TYPE_SWAPCHAIN_IMAGE_ACQUIRE_INFO: StructureType = 55;
+pub const TYPE_SWAPCHAIN_IMAGE_WAIT_INFO: StructureType = 56;
+pub const TYPE_SWAPCHAIN_IMAGE_RELEASE_INFO: StructureType = 57;
+pub const TYPE_ACTION_STATE_GET_INFO: StructureType = 58;
+pub const TYPE_SESSION_ACTION_SETS_ATTACH_INFO: StructureType = 60;
+pub const TYPE_ACTIONS_SYNC_INFO: StructureType = 61;
+pub const TYPE_COMPOSITION_LAYER_DEPTH_INFO_KHR: StructureType = 1_000_010_000;
+pub const TYPE_GRAPHICS_BINDING_OPENGL_WIN32_KHR: StructureType = 1_000_023_000;
+pub const TYPE_SWAPCHAIN_IMAGE_OPENGL_KHR: StructureType = 1_000_023_004;
+pub const TYPE_GRAPHICS_REQUIREMENTS_OPENGL_KHR: StructureType = 1_000_023_005;
+
+pub const FORM_FACTOR_HEAD_MOUNTED_DISPLAY: i32 = 1;
+pub const VIEW_CONFIGURATION_TYPE_PRIMARY_STEREO: i32 = 2;
+pub const ENVIRONMENT_BLEND_MODE_OPAQUE: i32 = 1;
+
+pub const REFERENCE_SPACE_TYPE_VIEW: i32 = 1;
+pub const REFERENCE_SPACE_TYPE_LOCAL: i32 = 2;
+pub const REFERENCE_SPACE_TYPE_STAGE: i32 = 3;
+
+pub const SESSION_STATE_UNKNOWN: i32 = 0;
+pub const SESSION_STATE_IDLE: i32 = 1;
+pub const SESSION_STATE_READY: i32 = 2;
+pub const SESSION_STATE_SYNCHRONIZED: i32 = 3;
+pub const SESSION_STATE_VISIBLE: i32 = 4;
+pub const SESSION_STATE_FOCUSED: i32 = 5;
+pub const SESSION_STATE_STOPPING: i32 = 6;
+pub const SESSION_STATE_LOSS_PENDING: i32 = 7;
+pub const SESSION_STATE_EXITING: i32 = 8;
+
+pub const ACTION_TYPE_BOOLEAN_INPUT: i32 = 1;
+pub const ACTION_TYPE_FLOAT_INPUT: i32 = 2;
+pub const ACTION_TYPE_VECTOR2F_INPUT: i32 = 3;
+pub const ACTION_TYPE_POSE_INPUT: i32 = 4;
+
+pub const SWAPCHAIN_USAGE_COLOR_ATTACHMENT_BIT: u64 = 0x0000_0001;
+pub const SWAPCHAIN_USAGE_DEPTH_STENCIL_ATTACHMENT_BIT: u64 = 0x0000_0002;
+pub const SWAPCHAIN_USAGE_TRANSFER_DST_BIT: u64 = 0x0000_0010;
+pub const SWAPCHAIN_USAGE_SAMPLED_BIT: u64 = 0x0000_0020;
+
+pub const SPACE_LOCATION_ORIENTATION_VALID_BIT: u64 = 0x0000_0001;
+pub const SPACE_LOCATION_POSITION_VALID_BIT: u64 = 0x0000_0002;
+
+pub const MAX_EXTENSION_NAME_SIZE: usize = 128;
+pub const MAX_APPLICATION_NAME_SIZE: usize = 128;
+pub const MAX_ENGINE_NAME_SIZE: usize = 128;
+pub const MAX_ACTION_SET_NAME_SIZE: usize = 64;
+pub const MAX_ACTION_NAME_SIZE: usize = 64;
+pub const MAX_LOCALIZED_ACTION_SET_NAME_SIZE: usize = 128;
+pub const MAX_LOCALIZED_ACTION_NAME_SIZE: usize = 128;
+
+pub const KHR_OPENGL_ENABLE_EXTENSION_NAME: &[u8] = b"XR_KHR_opengl_enable\0";
+pub const KHR_COMPOSITION_LAYER_DEPTH_EXTENSION_NAME: &[u8] = b"XR_KHR_composition_layer_depth\0";
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vector2f {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vector3f {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Quaternionf {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Posef {
+    pub orientation: Quaternionf,
+    pub position: Vector3f,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Fovf {
+    pub angleLeft: f32,
+    pub angleRight: f32,
+    pub angleUp: f32,
+    pub angleDown: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Offset2Di {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Extent2Di {
+    pub width: i32,
+    pub height: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Rect2Di {
+    pub offset: Offset2Di,
+    pub extent: Extent2Di,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ExtensionProperties {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub extensionName: [c_char; MAX_EXTENSION_NAME_SIZE],
+    pub extensionVersion: u32,
+}
+
+#[repr(C)]
+pub struct ApplicationInfo {
+    pub applicationName: [c_char; MAX_APPLICATION_NAME_SIZE],
+    pub applicationVersion: u32,
+    pub engineName: [c_char; MAX_ENGINE_NAME_SIZE],
+    pub engineVersion: u32,
+    pub apiVersion: Version,
+}
+
+#[repr(C)]
+pub struct InstanceCreateInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub createFlags: u64,
+    pub applicationInfo: ApplicationInfo,
+    pub enabledApiLayerCount: u32,
+    pub enabledApiLayerNames: *const *const c_char,
+    pub enabledExtensionCount: u32,
+    pub enabledExtensionNames: *const *const c_char,
+}
+
+#[repr(C)]
+pub struct SystemGetInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub formFactor: i32,
+}
+
+#[repr(C)]
+pub struct GraphicsRequirementsOpenGLKHR {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub minApiVersionSupported: Version,
+    pub maxApiVersionSupported: Version,
+}
+
+#[repr(C)]
+pub struct GraphicsBindingOpenGLWin32KHR {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub hDC: *mut c_void,
+    pub hGLRC: *mut c_void,
+}
+
+#[repr(C)]
+pub struct SessionCreateInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub createFlags: u64,
+    pub systemId: SystemId,
+}
+
+#[repr(C)]
+pub struct SessionBeginInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub primaryViewConfigurationType: i32,
+}
+
+#[repr(C)]
+pub struct ReferenceSpaceCreateInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub referenceSpaceType: i32,
+    pub poseInReferenceSpace: Posef,
+}
+
+#[repr(C)]
+pub struct ActionSpaceCreateInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub action: Action,
+    pub subactionPath: Path,
+    pub poseInActionSpace: Posef,
+}
+
+#[repr(C)]
+pub struct SpaceLocation {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub locationFlags: u64,
+    pub pose: Posef,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ViewConfigurationView {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub recommendedImageRectWidth: u32,
+    pub maxImageRectWidth: u32,
+    pub recommendedImageRectHeight: u32,
+    pub maxImageRectHeight: u32,
+    pub recommendedSwapchainSampleCount: u32,
+    pub maxSwapchainSampleCount: u32,
+}
+
+#[repr(C)]
+pub struct SwapchainCreateInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub createFlags: u64,
+    pub usageFlags: u64,
+    pub format: i64,
+    pub sampleCount: u32,
+    pub width: u32,
+    pub height: u32,
+    pub faceCount: u32,
+    pub arraySize: u32,
+    pub mipCount: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SwapchainImageOpenGLKHR {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub image: u32,
+}
+
+#[repr(C)]
+pub struct SwapchainImageAcquireInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+}
+
+#[repr(C)]
+pub struct SwapchainImageWaitInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub timeout: Duration,
+}
+
+#[repr(C)]
+pub struct SwapchainImageReleaseInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+}
+
+#[repr(C)]
+pub struct FrameWaitInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FrameState {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub predictedDisplayTime: Time,
+    pub predictedDisplayPeriod: Duration,
+    pub shouldRender: Bool32,
+}
+
+#[repr(C)]
+pub struct FrameBeginInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+}
+
+#[repr(C)]
+pub struct CompositionLayerBaseHeader {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub layerFlags: u64,
+    pub space: Space,
+}
+
+#[repr(C)]
+pub struct FrameEndInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub displayTime: Time,
+    pub environmentBlendMode: i32,
+    pub layerCount: u32,
+    pub layers: *const *const CompositionLayerBaseHeader,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SwapchainSubImage {
+    pub swapchain: Swapchain,
+    pub imageRect: Rect2Di,
+    pub imageArrayIndex: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CompositionLayerProjectionView {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub pose: Posef,
+    pub fov: Fovf,
+    pub subImage: SwapchainSubImage,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CompositionLayerDepthInfoKHR {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub subImage: SwapchainSubImage,
+    pub minDepth: f32,
+    pub maxDepth: f32,
+    pub nearZ: f32,
+    pub farZ: f32,
+}
+
+#[repr(C)]
+pub struct CompositionLayerProjection {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub layerFlags: u64,
+    pub space: Space,
+    pub viewCount: u32,
+    pub views: *const CompositionLayerProjectionView,
+}
+
+#[repr(C)]
+pub struct ViewLocateInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub viewConfigurationType: i32,
+    pub displayTime: Time,
+    pub space: Space,
+}
+
+#[repr(C)]
+pub struct ViewState {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub viewStateFlags: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct View {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub pose: Posef,
+    pub fov: Fovf,
+}
+
+#[repr(C)]
+pub struct EventDataBuffer {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub varying: [u8; 4000],
+}
+
+#[repr(C)]
+pub struct EventDataSessionStateChanged {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub session: Session,
+    pub state: i32,
+    pub time: Time,
+}
+
+#[repr(C)]
+pub struct ActionSetCreateInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub actionSetName: [c_char; MAX_ACTION_SET_NAME_SIZE],
+    pub localizedActionSetName: [c_char; MAX_LOCALIZED_ACTION_SET_NAME_SIZE],
+    pub priority: u32,
+}
+
+#[repr(C)]
+pub struct ActionCreateInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub actionName: [c_char; MAX_ACTION_NAME_SIZE],
+    pub actionType: i32,
+    pub countSubactionPaths: u32,
+    pub subactionPaths: *const Path,
+    pub localizedActionName: [c_char; MAX_LOCALIZED_ACTION_NAME_SIZE],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ActionSuggestedBinding {
+    pub action: Action,
+    pub binding: Path,
+}
+
+#[repr(C)]
+pub struct InteractionProfileSuggestedBinding {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub interactionProfile: Path,
+    pub countSuggestedBindings: u32,
+    pub suggestedBindings: *const ActionSuggestedBinding,
+}
+
+#[repr(C)]
+pub struct SessionActionSetsAttachInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub countActionSets: u32,
+    pub actionSets: *const ActionSet,
+}
+
+#[repr(C)]
+pub struct ActiveActionSet {
+    pub actionSet: ActionSet,
+    pub subactionPath: Path,
+}
+
+#[repr(C)]
+pub struct ActionsSyncInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub countActiveActionSets: u32,
+    pub activeActionSets: *const ActiveActionSet,
+}
+
+#[repr(C)]
+pub struct ActionStateGetInfo {
+    pub ty: StructureType,
+    pub next: *const c_void,
+    pub action: Action,
+    pub subactionPath: Path,
+}
+
+#[repr(C)]
+pub struct ActionStateBoolean {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub currentState: Bool32,
+    pub changedSinceLastSync: Bool32,
+    pub lastChangeTime: Time,
+    pub isActive: Bool32,
+}
+
+#[repr(C)]
+pub struct ActionStateFloat {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub currentState: f32,
+    pub changedSinceLastSync: Bool32,
+    pub lastChangeTime: Time,
+    pub isActive: Bool32,
+}
+
+#[repr(C)]
+pub struct ActionStateVector2f {
+    pub ty: StructureType,
+    pub next: *mut c_void,
+    pub currentState: Vector2f,
+    pub changedSinceLastSync: Bool32,
+    pub lastChangeTime: Time,
+    pub isActive: Bool32,
+}
+
+pub type VoidFunction = unsafe extern "system" fn();
+
+/// Function pointer types, named after the commands they point to.
+pub mod pfn {
+    use std::os::raw::c_char;
+    use super::*;
+
+    pub type xrGetInstanceProcAddr = unsafe extern "system" fn(Instance, *const c_char, *mut Option<VoidFunction>) -> Result;
+    pub type xrEnumerateInstanceExtensionProperties = unsafe extern "system" fn(*const c_char, u32, *mut u32, *mut ExtensionProperties) -> Result;
+    pub type xrCreateInstance = unsafe extern "system" fn(*const InstanceCreateInfo, *mut Instance) -> Result;
+    pub type xrDestroyInstance = unsafe extern "system" fn(Instance) -> Result;
+    pub type xrPollEvent = unsafe extern "system" fn(Instance, *mut EventDataBuffer) -> Result;
+    pub type xrStringToPath = unsafe extern "system" fn(Instance, *const c_char, *mut Path) -> Result;
+    pub type xrGetSystem = unsafe extern "system" fn(Instance, *const SystemGetInfo, *mut SystemId) -> Result;
+    pub type xrGetOpenGLGraphicsRequirementsKHR = unsafe extern "system" fn(Instance, SystemId, *mut GraphicsRequirementsOpenGLKHR) -> Result;
+    pub type xrEnumerateViewConfigurationViews = unsafe extern "system" fn(Instance, SystemId, i32, u32, *mut u32, *mut ViewConfigurationView) -> Result;
+    pub type xrCreateSession = unsafe extern "system" fn(Instance, *const SessionCreateInfo, *mut Session) -> Result;
+    pub type xrDestroySession = unsafe extern "system" fn(Session) -> Result;
+    pub type xrBeginSession = unsafe extern "system" fn(Session, *const SessionBeginInfo) -> Result;
+    pub type xrEndSession = unsafe extern "system" fn(Session) -> Result;
+    pub type xrCreateReferenceSpace = unsafe extern "system" fn(Session, *const ReferenceSpaceCreateInfo, *mut Space) -> Result;
+    pub type xrCreateActionSpace = unsafe extern "system" fn(Session, *const ActionSpaceCreateInfo, *mut Space) -> Result;
+    pub type xrDestroySpace = unsafe extern "system" fn(Space) -> Result;
+    pub type xrLocateSpace = unsafe extern "system" fn(Space, Space, Time, *mut SpaceLocation) -> Result;
+    pub type xrEnumerateSwapchainFormats = unsafe extern "system" fn(Session, u32, *mut u32, *mut i64) -> Result;
+    pub type xrCreateSwapchain = unsafe extern "system" fn(Session, *const SwapchainCreateInfo, *mut Swapchain) -> Result;
+    pub type xrDestroySwapchain = unsafe extern "system" fn(Swapchain) -> Result;
+    pub type xrEnumerateSwapchainImages = unsafe extern "system" fn(Swapchain, u32, *mut u32, *mut SwapchainImageOpenGLKHR) -> Result;
+    pub type xrAcquireSwapchainImage = unsafe extern "system" fn(Swapchain, *const SwapchainImageAcquireInfo, *mut u32) -> Result;
+    pub type xrWaitSwapchainImage = unsafe extern "system" fn(Swapchain, *const SwapchainImageWaitInfo) -> Result;
+    pub type xrReleaseSwapchainImage = unsafe extern "system" fn(Swapchain, *const SwapchainImageReleaseInfo) -> Result;
+    pub type xrWaitFrame = unsafe extern "system" fn(Session, *const FrameWaitInfo, *mut FrameState) -> Result;
+    pub type xrBeginFrame = unsafe extern "system" fn(Session, *const FrameBeginInfo) -> Result;
+    pub type xrEndFrame = unsafe extern "system" fn(Session, *const FrameEndInfo) -> Result;
+    pub type xrLocateViews = unsafe extern "system" fn(Session, *const ViewLocateInfo, *mut ViewState, u32, *mut u32, *mut View) -> Result;
+    pub type xrCreateActionSet = unsafe extern "system" fn(Instance, *const ActionSetCreateInfo, *mut ActionSet) -> Result;
+    pub type xrCreateAction = unsafe extern "system" fn(ActionSet, *const ActionCreateInfo, *mut Action) -> Result;
+    pub type xrSuggestInteractionProfileBindings = unsafe extern "system" fn(Instance, *const InteractionProfileSuggestedBinding) -> Result;
+    pub type xrAttachSessionActionSets = unsafe extern "system" fn(Session, *const SessionActionSetsAttachInfo) -> Result;
+    pub type xrSyncActions = unsafe extern "system" fn(Session, *const ActionsSyncInfo) -> Result;
+    pub type xrGetActionStateBoolean = unsafe extern "system" fn(Session, *const ActionStateGetInfo, *mut ActionStateBoolean) -> Result;
+    pub type xrGetActionStateFloat = unsafe extern "system" fn(Session, *const ActionStateGetInfo, *mut ActionStateFloat) -> Result;
+    pub type xrGetActionStateVector2f = unsafe extern "system" fn(Session, *const ActionStateGetInfo, *mut ActionStateVector2f) -> Result;
+}
@@ -0,0 +1,400 @@
+//! The Oculus LibOVR runtime.
+
+use std::mem;
+use std::ptr;
+
+use vr;
+
+use timing::{Clock, FramePacer, FrameStats};
+use super::{Error, Eye, EyeTextures, Fov, FrameStart, InputState, Pose, TrackingOrigin, VrBackend};
+
+fn check(call: &'static str, result: vr::ovrResult) -> Result<(), Error> {
+    if vr::OVR_FAILURE(result) {
+        Err(Error::Call { call, code: result })
+    } else {
+        Ok(())
+    }
+}
+
+fn to_fov(fov: &vr::ovrFovPort) -> Fov {
+    Fov {
+        up: fov.UpTan,
+        down: fov.DownTan,
+        left: fov.LeftTan,
+        right: fov.RightTan,
+    }
+}
+
+fn to_pose(pose: &vr::ovrPosef) -> Pose {
+    Pose {
+        position: [pose.Position.x, pose.Position.y, pose.Position.z],
+        orientation: [pose.Orientation.x, pose.Orientation.y, pose.Orientation.z, pose.Orientation.w],
+    }
+}
+
+/// LibOVR's clock, which its display and sensor sample times are given in.
+struct OvrClock;
+
+impl Clock for OvrClock {
+    fn now(&self) -> f64 {
+        unsafe { vr::ovr_GetTimeInSeconds() }
+    }
+}
+
+/// LibOVR's `ovrLayerType_EyeFovDepth`, which ovr-sys leaves out.
+const ovrLayerType_EyeFovDepth: vr::ovrLayerType = 2;
+
+/// LibOVR's `ovrLayerEyeFovDepth`, which ovr-sys leaves out: an
+/// `ovrLayerEyeFov` followed by the depth the compositor reprojects with.
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(non_camel_case_types, non_snake_case)]
+struct ovrLayerEyeFovDepth {
+    _align: [isize; 0],
+    Header: vr::ovrLayerHeader,
+    ColorTexture: [vr::ovrTextureSwapChain; 2],
+    Viewport: [vr::ovrRecti; 2],
+    Fov: [vr::ovrFovPort; 2],
+    RenderPose: [vr::ovrPosef; 2],
+    SensorSampleTime: f64,
+    DepthTexture: [vr::ovrTextureSwapChain; 2],
+    ProjectionDesc: vr::ovrTimewarpProjectionDesc,
+}
+
+pub struct OvrBackend {
+    session: vr::ovrSession,
+    swap_chains: Option<[vr::ovrTextureSwapChain; 2]>,
+    depth_swap_chains: Option<[vr::ovrTextureSwapChain; 2]>,
+    /// Near and far clip distances in meters, if known.
+    depth_range: Option<(f32, f32)>,
+    size: (u32, u32),
+    poses: [vr::ovrPosef; 2],
+    /// The headset's default per-eye FOV, used for rendering and submission alike.
+    fov: [vr::ovrFovPort; 2],
+    pacer: FramePacer,
+    /// The session status flags last seen, to log when they change.
+    status: Option<SessionStatus>,
+}
+
+/// The parts of `ovrSessionStatus` worth logging changes to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SessionStatus {
+    is_visible: bool,
+    hmd_present: bool,
+    hmd_mounted: bool,
+    display_lost: bool,
+    should_quit: bool,
+}
+
+impl SessionStatus {
+    fn new(status: &vr::ovrSessionStatus) -> SessionStatus {
+        SessionStatus {
+            is_visible: status.IsVisible != 0,
+            hmd_present: status.HmdPresent != 0,
+            hmd_mounted: status.HmdMounted != 0,
+            display_lost: status.DisplayLost != 0,
+            should_quit: status.ShouldQuit != 0,
+        }
+    }
+}
+
+unsafe impl Send for OvrBackend {}
+
+impl OvrBackend {
+    pub fn new(origin: TrackingOrigin) -> Result<OvrBackend, Error> {
+        unsafe {
+            let init = vr::ovrInitParams {
+                Flags: 0,
+                RequestedMinorVersion: vr::OVR_MINOR_VERSION,
+                LogCallback: None,
+                UserData: 0,
+                ConnectionTimeoutMS: 0,
+                .. mem::zeroed()
+            };
+            check("ovr_Initialize", vr::ovr_Initialize(&init))?;
+            let mut session = ptr::null_mut();
+            let mut luid = mem::zeroed();
+            if let Err(error) = check("ovr_Create", vr::ovr_Create(&mut session, &mut luid)) {
+                vr::ovr_Shutdown();
+                return Err(error);
+            }
+            vr::ovr_SetTrackingOriginType(session, match origin {
+                TrackingOrigin::Floor => vr::ovrTrackingOrigin_FloorLevel,
+                TrackingOrigin::Eye => vr::ovrTrackingOrigin_EyeLevel,
+            });
+            let desc = vr::ovr_GetHmdDesc(session);
+            Ok(OvrBackend {
+                session,
+                swap_chains: None,
+                depth_swap_chains: None,
+                depth_range: None,
+                size: (0, 0),
+                poses: [mem::zeroed(); 2],
+                fov: desc.DefaultEyeFov,
+                pacer: FramePacer::new(Box::new(OvrClock)),
+                status: None,
+            })
+        }
+    }
+
+    fn texture_swap_chain(&self, format: vr::ovrTextureFormat, width: u32, height: u32) -> Result<vr::ovrTextureSwapChain, Error> {
+        let desc = vr::ovrTextureSwapChainDesc {
+            Type: vr::ovrTexture_2D,
+            Format: format,
+            ArraySize: 1,
+            Width: width as i32,
+            Height: height as i32,
+            MipLevels: 1,
+            SampleCount: 1,
+            StaticImage: 0,
+            MiscFlags: 0,
+            BindFlags: 0
+        };
+        unsafe {
+            let mut tsc = ptr::null_mut();
+            check("ovr_CreateTextureSwapChainGL", vr::opengl::ovr_CreateTextureSwapChainGL(self.session, &desc, &mut tsc))?;
+            Ok(tsc)
+        }
+    }
+
+    /// A swap chain per eye, or neither.
+    fn texture_swap_chains(&self, format: vr::ovrTextureFormat, width: u32, height: u32) -> Result<[vr::ovrTextureSwapChain; 2], Error> {
+        let left = self.texture_swap_chain(format, width, height)?;
+        match self.texture_swap_chain(format, width, height) {
+            Ok(right) => Ok([left, right]),
+            Err(error) => {
+                unsafe {
+                    vr::ovr_DestroyTextureSwapChain(self.session, left);
+                }
+                Err(error)
+            },
+        }
+    }
+
+    fn destroy_swap_chains(&mut self) {
+        for swap_chains in self.swap_chains.take().iter().chain(self.depth_swap_chains.take().iter()) {
+            for tsc in swap_chains {
+                unsafe {
+                    vr::ovr_DestroyTextureSwapChain(self.session, *tsc);
+                }
+            }
+        }
+    }
+
+    fn layer(&self, swap_chains: &[vr::ovrTextureSwapChain; 2]) -> vr::ovrLayerEyeFov {
+        let (width, height) = self.size;
+        unsafe {
+            let viewport = vr::ovrRecti {
+                Pos: vr::ovrVector2i {
+                    x: 0,
+                    y: 0,
+                    .. mem::zeroed()
+                },
+                Size: vr::ovrSizei {
+                    w: width as i32,
+                    h: height as i32,
+                    .. mem::zeroed()
+                },
+                .. mem::zeroed()
+            };
+            vr::ovrLayerEyeFov {
+                Header: vr::ovrLayerHeader {
+                    Type: vr::ovrLayerType_EyeFov,
+                    Flags: vr::ovrLayerFlag_TextureOriginAtBottomLeft as u32,
+                    .. mem::zeroed()
+                },
+                ColorTexture: *swap_chains,
+                Viewport: [viewport, viewport],
+                Fov: self.fov,
+                RenderPose: self.poses,
+                SensorSampleTime: self.pacer.current().and_then(|frame| frame.sensor_sample).unwrap_or(0.0),
+                .. mem::zeroed()
+            }
+        }
+    }
+
+    /// `layer` with depth, for a depth buffer drawn with the given clip distances.
+    fn depth_layer(&self, layer: &vr::ovrLayerEyeFov, depth_swap_chains: &[vr::ovrTextureSwapChain; 2], near: f32, far: f32) -> ovrLayerEyeFovDepth {
+        let flags = vr::ovrProjection_ClipRangeOpenGL as u32;
+        unsafe {
+            let projection = vr::ovrMatrix4f_Projection(self.fov[0], near, far, flags);
+            ovrLayerEyeFovDepth {
+                _align: [],
+                Header: vr::ovrLayerHeader {
+                    Type: ovrLayerType_EyeFovDepth,
+                    .. layer.Header
+                },
+                ColorTexture: layer.ColorTexture,
+                Viewport: layer.Viewport,
+                Fov: layer.Fov,
+                RenderPose: layer.RenderPose,
+                SensorSampleTime: layer.SensorSampleTime,
+                DepthTexture: *depth_swap_chains,
+                ProjectionDesc: vr::ovrTimewarpProjectionDesc_FromProjection(projection, flags),
+            }
+        }
+    }
+}
+
+impl VrBackend for OvrBackend {
+    fn name(&self) -> &'static str {
+        "LibOVR"
+    }
+
+    fn begin_frame(&mut self) -> Result<FrameStart, Error> {
+        let recentered;
+        unsafe {
+            let mut status = mem::zeroed();
+            check("ovr_GetSessionStatus", vr::ovr_GetSessionStatus(self.session, &mut status))?;
+            let changed = SessionStatus::new(&status);
+            if self.status != Some(changed) {
+                info!("session status: {:?}", changed);
+                self.status = Some(changed);
+            }
+            recentered = status.ShouldRecenter != 0;
+            if recentered {
+                vr::ovr_RecenterTrackingOrigin(self.session);
+            }
+            let index = self.pacer.begin_frame();
+            self.pacer.set_predicted_display_time(vr::ovr_GetPredictedDisplayTime(self.session, index as i64));
+        }
+        Ok(FrameStart { render: true, recentered })
+    }
+
+    fn eye_poses(&mut self) -> Result<[Pose; 2], Error> {
+        unsafe {
+            let left_eye_hmd_offset = vr::ovr_GetRenderDesc(self.session, vr::ovrEye_Left, self.fov[0]).HmdToEyeOffset;
+            let right_eye_hmd_offset = vr::ovr_GetRenderDesc(self.session, vr::ovrEye_Right, self.fov[1]).HmdToEyeOffset;
+            // 0 asks for the latest reading, should there be no frame.
+            let display_time = self.pacer.current().and_then(|frame| frame.predicted_display).unwrap_or(0.0);
+            self.pacer.sample_sensors();
+            let state = vr::ovr_GetTrackingState(self.session, display_time, vr::ovrTrue);
+            vr::ovr_CalcEyePoses(state.HeadPose.ThePose, &[left_eye_hmd_offset, right_eye_hmd_offset], &mut self.poses as *mut _ as *const _);
+        }
+        Ok([to_pose(&self.poses[0]), to_pose(&self.poses[1])])
+    }
+
+    fn eye_fov(&mut self, eye: Eye) -> Result<Fov, Error> {
+        Ok(to_fov(&self.fov[eye.index()]))
+    }
+
+    fn recommended_size(&mut self, pixel_density: f32) -> Result<(u32, u32), Error> {
+        let (mut width, mut height) = (0, 0);
+        for (eye, &fov) in [vr::ovrEye_Left, vr::ovrEye_Right].iter().zip(&self.fov) {
+            let size = unsafe { vr::ovr_GetFovTextureSize(self.session, *eye, fov, pixel_density) };
+            width = width.max(size.w as u32);
+            height = height.max(size.h as u32);
+        }
+        Ok((width, height))
+    }
+
+    fn create_swap_chains(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        self.destroy_swap_chains();
+        self.swap_chains = Some(self.texture_swap_chains(vr::OVR_FORMAT_R8G8B8A8_UNORM_SRGB, width, height)?);
+        // Without depth the compositor still does orientation timewarp, so
+        // a runtime that won't make depth chains isn't worth failing over.
+        self.depth_swap_chains = self.texture_swap_chains(vr::OVR_FORMAT_D32_FLOAT, width, height)
+            .map_err(|error| warn!("no depth swap chains, so no positional timewarp: {}", error))
+            .ok();
+        self.size = (width, height);
+        Ok(())
+    }
+
+    fn acquire_eye_textures(&mut self, eye: Eye) -> Result<EyeTextures, Error> {
+        let tsc = match self.swap_chains {
+            Some(ref swap_chains) => swap_chains[eye.index()],
+            None => return Err(Error::Call { call: "ovr_GetTextureSwapChainBufferGL", code: vr::ovrError_InvalidParameter }),
+        };
+        let buffer = |tsc| {
+            let mut texid = 0;
+            unsafe {
+                check("ovr_GetTextureSwapChainBufferGL", vr::opengl::ovr_GetTextureSwapChainBufferGL(self.session, tsc, -1, &mut texid))?;
+            }
+            Ok(texid)
+        };
+        Ok(EyeTextures {
+            color: buffer(tsc)?,
+            depth: match self.depth_swap_chains {
+                Some(ref depth_swap_chains) => Some(buffer(depth_swap_chains[eye.index()])?),
+                None => None,
+            },
+        })
+    }
+
+    fn commit(&mut self, eye: Eye) -> Result<(), Error> {
+        for swap_chains in self.swap_chains.iter().chain(self.depth_swap_chains.iter()) {
+            unsafe {
+                check("ovr_CommitTextureSwapChain", vr::ovr_CommitTextureSwapChain(self.session, swap_chains[eye.index()]))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_depth_range(&mut self, range: Option<(f32, f32)>) {
+        self.depth_range = range;
+    }
+
+    fn submit(&mut self) -> Result<(), Error> {
+        if let Some(swap_chains) = self.swap_chains {
+            let layer = self.layer(&swap_chains);
+            let depth_layer = match (self.depth_swap_chains, self.depth_range) {
+                (Some(ref depth_swap_chains), Some((near, far))) => Some(self.depth_layer(&layer, depth_swap_chains, near, far)),
+                _ => None,
+            };
+            let header = match depth_layer {
+                Some(ref depth_layer) => &depth_layer.Header,
+                None => &layer.Header,
+            };
+            let layers = [header as *const vr::ovrLayerHeader];
+            unsafe {
+                check("ovr_SubmitFrame", vr::ovr_SubmitFrame(self.session, self.pacer.frame_index() as i64, ptr::null(), layers.as_ptr(), 1))?;
+            }
+        }
+        self.pacer.end_frame();
+        Ok(())
+    }
+
+    fn take_frame_stats(&mut self) -> FrameStats {
+        self.pacer.take_stats()
+    }
+
+    fn poll_input(&mut self) -> Result<InputState, Error> {
+        unsafe {
+            let mut state: vr::ovrInputState = mem::zeroed();
+            check("ovr_GetInputState", vr::ovr_GetInputState(self.session, vr::ovrControllerType_Touch, &mut state))?;
+            let tracking = vr::ovr_GetTrackingState(self.session, 0.0, vr::ovrFalse);
+            let hand = |hand: usize| if tracking.HandStatusFlags[hand] & vr::ovrStatus_OrientationTracked as u32 != 0 {
+                Some(to_pose(&tracking.HandPoses[hand].ThePose))
+            } else {
+                None
+            };
+            Ok(InputState {
+                buttons: state.Buttons,
+                touches: state.Touches,
+                index_trigger: state.IndexTrigger,
+                hand_trigger: state.HandTrigger,
+                thumbstick: [
+                    [state.Thumbstick[0].x, state.Thumbstick[0].y],
+                    [state.Thumbstick[1].x, state.Thumbstick[1].y],
+                ],
+                hands: [hand(0), hand(1)],
+            })
+        }
+    }
+
+    fn recenter(&mut self) -> Result<(), Error> {
+        unsafe {
+            check("ovr_RecenterTrackingOrigin", vr::ovr_RecenterTrackingOrigin(self.session))
+        }
+    }
+}
+
+impl Drop for OvrBackend {
+    fn drop(&mut self) {
+        self.destroy_swap_chains();
+        unsafe {
+            vr::ovr_Destroy(self.session);
+            vr::ovr_Shutdown();
+        }
+    }
+}
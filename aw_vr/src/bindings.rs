@@ -0,0 +1,400 @@
+//! What the Touch controllers do, as bindings from their inputs to keys, the
+//! mouse and AW_VR's own actions.
+//!
+//! Every input reads as a value from 0 to 1: buttons and touches are 0 or 1,
+//! triggers and grips how far they're pulled, and a stick direction how far
+//! the stick is pushed that way. A binding is pressed once its input reaches
+//! its threshold, and stays pressed until the input falls below its release
+//! point. Its mode then decides when its target is active.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::{self, Deserialize, Deserializer};
+
+use backend::{button, touch, InputState};
+use keyboard::{Key, Mouse};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+impl Hand {
+    fn index(self) -> usize {
+        match self {
+            Hand::Left => 0,
+            Hand::Right => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// A controller input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    /// An `InputState::buttons` bit.
+    Button(u32),
+    /// An `InputState::touches` bit.
+    Touch(u32),
+    Trigger(Hand),
+    Grip(Hand),
+    Stick(Hand, Direction),
+}
+
+/// Inputs by the names they have in the config.
+const INPUTS: &[(&str, Input)] = &[
+    ("a", Input::Button(button::A)),
+    ("b", Input::Button(button::B)),
+    ("x", Input::Button(button::X)),
+    ("y", Input::Button(button::Y)),
+    ("left_stick_click", Input::Button(button::LTHUMB)),
+    ("right_stick_click", Input::Button(button::RTHUMB)),
+    ("menu", Input::Button(button::ENTER)),
+    ("a_touch", Input::Touch(touch::A)),
+    ("b_touch", Input::Touch(touch::B)),
+    ("x_touch", Input::Touch(touch::X)),
+    ("y_touch", Input::Touch(touch::Y)),
+    ("left_stick_touch", Input::Touch(touch::LTHUMB)),
+    ("right_stick_touch", Input::Touch(touch::RTHUMB)),
+    ("left_trigger_touch", Input::Touch(touch::LINDEX_TRIGGER)),
+    ("right_trigger_touch", Input::Touch(touch::RINDEX_TRIGGER)),
+    ("left_thumbrest_touch", Input::Touch(touch::LTHUMB_REST)),
+    ("right_thumbrest_touch", Input::Touch(touch::RTHUMB_REST)),
+    ("left_trigger", Input::Trigger(Hand::Left)),
+    ("right_trigger", Input::Trigger(Hand::Right)),
+    ("left_grip", Input::Grip(Hand::Left)),
+    ("right_grip", Input::Grip(Hand::Right)),
+    ("left_stick_up", Input::Stick(Hand::Left, Direction::Up)),
+    ("left_stick_down", Input::Stick(Hand::Left, Direction::Down)),
+    ("left_stick_left", Input::Stick(Hand::Left, Direction::Left)),
+    ("left_stick_right", Input::Stick(Hand::Left, Direction::Right)),
+    ("right_stick_up", Input::Stick(Hand::Right, Direction::Up)),
+    ("right_stick_down", Input::Stick(Hand::Right, Direction::Down)),
+    ("right_stick_left", Input::Stick(Hand::Right, Direction::Left)),
+    ("right_stick_right", Input::Stick(Hand::Right, Direction::Right)),
+];
+
+impl Input {
+    /// How far the input is pressed, from 0 to 1.
+    pub fn value(self, state: &InputState) -> f32 {
+        let bit = |bits: u32, bit: u32| if bits & bit != 0 { 1.0 } else { 0.0 };
+        match self {
+            Input::Button(button) => bit(state.buttons, button),
+            Input::Touch(touch) => bit(state.touches, touch),
+            Input::Trigger(hand) => state.index_trigger[hand.index()],
+            Input::Grip(hand) => state.hand_trigger[hand.index()],
+            Input::Stick(hand, direction) => {
+                let [x, y] = state.thumbstick[hand.index()];
+                match direction {
+                    Direction::Up => y,
+                    Direction::Down => -y,
+                    Direction::Left => -x,
+                    Direction::Right => x,
+                }.max(0.0)
+            },
+        }
+    }
+}
+
+impl FromStr for Input {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Input, String> {
+        INPUTS.iter()
+            .find(|&&(other, _)| other == name)
+            .map(|&(_, input)| input)
+            .ok_or_else(|| format!("unknown input {:?}", name))
+    }
+}
+
+impl<'de> Deserialize<'de> for Input {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Input, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match INPUTS.iter().find(|&&(_, input)| input == *self) {
+            Some(&(name, _)) => write!(f, "{}", name),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// Things AW_VR itself does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VrAction {
+    /// Lines the view up with the avatar again.
+    Recenter,
+}
+
+/// What a binding does while it's active.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    /// Holds these keys, in order, as a chord.
+    Keys(Vec<Key>),
+    /// Holds a mouse button, or turns the wheel a notch each time the binding
+    /// becomes active.
+    Mouse(Mouse),
+    /// Does this each time the binding becomes active.
+    Vr(VrAction),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Active while pressed.
+    #[default]
+    Hold,
+    /// Each press turns it on or off.
+    Toggle,
+    /// Active for one frame per press.
+    Tap,
+}
+
+/// How far below its threshold an input has to fall, unless a binding says
+/// otherwise, before the binding is released.
+pub const DEFAULT_HYSTERESIS: f32 = 0.05;
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "RawBinding")]
+pub struct Binding {
+    pub input: Input,
+    pub target: Target,
+    pub threshold: f32,
+    /// Where the input releases the binding again, at most `threshold`.
+    pub release: f32,
+    pub mode: Mode,
+}
+
+impl Binding {
+    /// A hold binding that releases a little below `threshold`.
+    pub fn new(input: Input, target: Target, threshold: f32) -> Binding {
+        Binding { input, target, threshold, release: (threshold - DEFAULT_HYSTERESIS).max(0.0), mode: Mode::Hold }
+    }
+
+    /// What's wrong with the binding, if anything.
+    pub fn check(&self) -> Result<(), &'static str> {
+        if !(self.threshold > 0.0 && self.threshold <= 1.0) {
+            return Err("threshold must be more than 0 and at most 1");
+        }
+        if !(self.release >= 0.0 && self.release <= self.threshold) {
+            return Err("release must be at least 0 and at most the threshold");
+        }
+        match self.target {
+            Target::Keys(ref keys) if keys.is_empty() => Err("keys can't be empty"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A binding as written in the config, with its target in one of three
+/// fields.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawBinding {
+    input: Input,
+    keys: Option<Vec<Key>>,
+    mouse: Option<Mouse>,
+    action: Option<VrAction>,
+    threshold: Option<f32>,
+    release: Option<f32>,
+    #[serde(default)]
+    mode: Mode,
+}
+
+impl TryFrom<RawBinding> for Binding {
+    type Error = String;
+
+    fn try_from(raw: RawBinding) -> Result<Binding, String> {
+        let target = match (raw.keys, raw.mouse, raw.action) {
+            (Some(keys), None, None) => Target::Keys(keys),
+            (None, Some(mouse), None) => Target::Mouse(mouse),
+            (None, None, Some(action)) => Target::Vr(action),
+            _ => return Err(format!("the binding for {} needs exactly one of keys, mouse or action", raw.input)),
+        };
+        let mut binding = Binding::new(raw.input, target, raw.threshold.unwrap_or(0.5));
+        if let Some(release) = raw.release {
+            binding.release = release;
+        }
+        binding.mode = raw.mode;
+        Ok(binding)
+    }
+}
+
+/// What the bindings want done this frame.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Output {
+    /// Every key to hold, in the order the bindings list them.
+    pub keys: Vec<Key>,
+    /// Mouse buttons to hold.
+    pub buttons: Vec<Mouse>,
+    /// Wheel notches to turn.
+    pub wheel: Vec<Mouse>,
+    pub actions: Vec<VrAction>,
+}
+
+/// Turns controller input into `Output`, frame by frame.
+pub struct InputMapper {
+    bindings: Vec<Binding>,
+    pressed: Vec<bool>,
+    toggled: Vec<bool>,
+    active: Vec<bool>,
+}
+
+impl InputMapper {
+    pub fn new(bindings: Vec<Binding>) -> InputMapper {
+        let count = bindings.len();
+        InputMapper { bindings, pressed: vec![false; count], toggled: vec![false; count], active: vec![false; count] }
+    }
+
+    /// Reads a frame's input.
+    pub fn update(&mut self, state: &InputState) -> Output {
+        let mut output = Output::default();
+        for (index, binding) in self.bindings.iter().enumerate() {
+            let value = binding.input.value(state);
+            let was_pressed = self.pressed[index];
+            let pressed = if was_pressed { value >= binding.release } else { value >= binding.threshold };
+            let pushed = pressed && !was_pressed;
+            self.pressed[index] = pressed;
+            let active = match binding.mode {
+                Mode::Hold => pressed,
+                Mode::Toggle => {
+                    if pushed {
+                        self.toggled[index] = !self.toggled[index];
+                    }
+                    self.toggled[index]
+                },
+                Mode::Tap => pushed,
+            };
+            let activated = active && !self.active[index];
+            self.active[index] = active;
+            match binding.target {
+                Target::Keys(ref keys) if active => {
+                    for &key in keys {
+                        if !output.keys.contains(&key) {
+                            output.keys.push(key);
+                        }
+                    }
+                },
+                Target::Mouse(wheel) if activated && wheel.is_wheel() => output.wheel.push(wheel),
+                Target::Mouse(button) if active && !button.is_wheel() && !output.buttons.contains(&button) => output.buttons.push(button),
+                Target::Vr(action) if activated => output.actions.push(action),
+                _ => {},
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stick(left: [f32; 2], right: [f32; 2]) -> InputState {
+        InputState { thumbstick: [left, right], ..InputState::default() }
+    }
+
+    fn keys(keys: &[Key]) -> Target {
+        Target::Keys(keys.to_vec())
+    }
+
+    #[test]
+    fn inputs_read_from_zero_to_one() {
+        let state = InputState {
+            buttons: button::A,
+            touches: touch::RTHUMB_REST,
+            index_trigger: [0.25, 0.0],
+            hand_trigger: [0.0, 0.75],
+            thumbstick: [[-0.5, 0.0], [0.0, 1.0]],
+            hands: [None, None],
+        };
+        let value = |name: &str| name.parse::<Input>().unwrap().value(&state);
+        assert_eq!(value("a"), 1.0);
+        assert_eq!(value("b"), 0.0);
+        assert_eq!(value("right_thumbrest_touch"), 1.0);
+        assert_eq!(value("left_trigger"), 0.25);
+        assert_eq!(value("right_grip"), 0.75);
+        assert_eq!(value("left_stick_left"), 0.5);
+        assert_eq!(value("left_stick_right"), 0.0);
+        assert_eq!(value("right_stick_up"), 1.0);
+        assert!("left_stick_sideways".parse::<Input>().is_err());
+        assert_eq!(Input::Grip(Hand::Left).to_string(), "left_grip");
+    }
+
+    #[test]
+    fn hysteresis_keeps_a_wobbly_stick_pressed() {
+        let mut binding = Binding::new(Input::Stick(Hand::Left, Direction::Up), keys(&[Key::Up]), 0.5);
+        binding.release = 0.3;
+        let mut mapper = InputMapper::new(vec![binding]);
+        assert_eq!(mapper.update(&stick([0.0, 0.45], [0.0, 0.0])).keys, vec![]);
+        assert_eq!(mapper.update(&stick([0.0, 0.5], [0.0, 0.0])).keys, vec![Key::Up]);
+        assert_eq!(mapper.update(&stick([0.0, 0.35], [0.0, 0.0])).keys, vec![Key::Up]);
+        assert_eq!(mapper.update(&stick([0.0, 0.25], [0.0, 0.0])).keys, vec![]);
+        assert_eq!(mapper.update(&stick([0.0, 0.45], [0.0, 0.0])).keys, vec![]);
+    }
+
+    #[test]
+    fn chords_and_shared_keys_are_held_once() {
+        let mut mapper = InputMapper::new(vec![
+            Binding::new(Input::Button(button::A), keys(&[Key::Control, Key::Char('c')]), 0.5),
+            Binding::new(Input::Button(button::B), keys(&[Key::Control, Key::Char('v')]), 0.5),
+        ]);
+        let output = mapper.update(&InputState { buttons: button::A | button::B, ..InputState::default() });
+        assert_eq!(output.keys, vec![Key::Control, Key::Char('c'), Key::Char('v')]);
+    }
+
+    #[test]
+    fn toggles_flip_on_each_press_and_taps_last_a_frame() {
+        let mut toggle = Binding::new(Input::Button(button::X), Target::Mouse(Mouse::Left), 0.5);
+        toggle.mode = Mode::Toggle;
+        let mut tap = Binding::new(Input::Button(button::Y), keys(&[Key::Space]), 0.5);
+        tap.mode = Mode::Tap;
+        let mut mapper = InputMapper::new(vec![toggle, tap]);
+        let press = |buttons| InputState { buttons, ..InputState::default() };
+
+        let output = mapper.update(&press(button::X | button::Y));
+        assert_eq!((output.buttons, output.keys), (vec![Mouse::Left], vec![Key::Space]));
+        let output = mapper.update(&press(button::X | button::Y));
+        assert_eq!((output.buttons, output.keys), (vec![Mouse::Left], vec![]));
+        assert_eq!(mapper.update(&press(0)).buttons, vec![Mouse::Left]);
+        assert_eq!(mapper.update(&press(button::X)).buttons, vec![]);
+    }
+
+    #[test]
+    fn actions_and_wheel_notches_happen_once_per_press() {
+        let mut mapper = InputMapper::new(vec![
+            Binding::new(Input::Button(button::ENTER), Target::Vr(VrAction::Recenter), 0.5),
+            Binding::new(Input::Trigger(Hand::Right), Target::Mouse(Mouse::WheelUp), 0.5),
+        ]);
+        let state = InputState { buttons: button::ENTER, index_trigger: [0.0, 0.9], ..InputState::default() };
+        let output = mapper.update(&state);
+        assert_eq!((output.actions, output.wheel), (vec![VrAction::Recenter], vec![Mouse::WheelUp]));
+        assert_eq!(mapper.update(&state), Output::default());
+    }
+
+    #[test]
+    fn bindings_are_checked() {
+        let mut binding = Binding::new(Input::Button(button::A), keys(&[]), 0.5);
+        assert!(binding.check().is_err());
+        binding.target = keys(&[Key::Space]);
+        assert_eq!(binding.check(), Ok(()));
+        binding.release = 0.6;
+        assert!(binding.check().is_err());
+        binding.threshold = 0.0;
+        assert!(binding.check().is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! Works out which camera draws are eye passes.
+//!
+//! AW draws more than its world camera: the minimap, avatar portraits and
+//! picking passes all go through the same RenderWare calls. Only the main
+//! camera's draws alternate eyes; everything else is left alone. The main
+//! camera is the one AW resizes with its window, or failing that the first one
+//! drawn. If the main camera stops being drawn while others keep going, AW has
+//! replaced it, and whichever other camera is drawn most takes over.
+
+use backend::Eye;
+
+/// How many draws by one other camera, with none by the main camera in
+/// between, it takes for that camera to become the main one.
+const ADOPT_AFTER: usize = 16;
+
+pub struct CameraTracker {
+    main: Option<usize>,
+    eye: Eye,
+    /// Whether the main camera has begun a draw it hasn't ended.
+    open: bool,
+    /// Draws by each other camera since the main camera last drew.
+    others: Vec<(usize, usize)>,
+}
+
+impl CameraTracker {
+    pub fn new() -> CameraTracker {
+        CameraTracker {
+            main: None,
+            eye: Eye::Left,
+            open: false,
+            others: Vec::new(),
+        }
+    }
+
+    pub fn main(&self) -> Option<usize> {
+        self.main
+    }
+
+    /// Makes `camera` the main camera, starting over from the left eye if it
+    /// wasn't already.
+    pub fn set_main(&mut self, camera: usize) {
+        if self.main != Some(camera) {
+            self.main = Some(camera);
+            self.eye = Eye::Left;
+            self.open = false;
+        }
+        self.others.clear();
+    }
+
+    /// A camera begins drawing. Returns the eye it is drawing, or `None` if it
+    /// isn't the main camera.
+    pub fn begin(&mut self, camera: usize) -> Option<Eye> {
+        match self.main {
+            Some(main) if main == camera => {},
+            Some(_) => {
+                let draws = match self.others.iter().position(|&(other, _)| other == camera) {
+                    Some(index) => {
+                        self.others[index].1 += 1;
+                        self.others[index].1
+                    },
+                    None => {
+                        self.others.push((camera, 1));
+                        1
+                    },
+                };
+                if draws < ADOPT_AFTER {
+                    return None;
+                }
+                self.set_main(camera);
+            },
+            None => self.set_main(camera),
+        }
+        self.others.clear();
+        if self.open {
+            // The last draw never ended, so which eye comes next is anyone's
+            // guess. Start a fresh frame.
+            self.eye = Eye::Left;
+        }
+        self.open = true;
+        Some(self.eye)
+    }
+
+    /// A camera finishes drawing. Returns the eye it drew, or `None` if it
+    /// isn't the main camera or never began.
+    pub fn end(&mut self, camera: usize) -> Option<Eye> {
+        if self.main != Some(camera) || !self.open {
+            return None;
+        }
+        self.open = false;
+        let eye = self.eye;
+        self.eye = Eye::from_index(eye.index() + 1);
+        Some(eye)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORLD: usize = 0x1000;
+    const MINIMAP: usize = 0x2000;
+    const PORTRAIT: usize = 0x3000;
+
+    fn draw(tracker: &mut CameraTracker, camera: usize) -> Option<Eye> {
+        let began = tracker.begin(camera);
+        assert_eq!(tracker.end(camera), began);
+        began
+    }
+
+    #[test]
+    fn main_camera_alternates_eyes() {
+        let mut tracker = CameraTracker::new();
+        let eyes: Vec<_> = (0..4).map(|_| draw(&mut tracker, WORLD)).collect();
+        assert_eq!(eyes, vec![Some(Eye::Left), Some(Eye::Right), Some(Eye::Left), Some(Eye::Right)]);
+    }
+
+    #[test]
+    fn other_cameras_dont_swap_the_eyes() {
+        let mut tracker = CameraTracker::new();
+        assert_eq!(draw(&mut tracker, WORLD), Some(Eye::Left));
+        assert_eq!(draw(&mut tracker, MINIMAP), None);
+        assert_eq!(draw(&mut tracker, PORTRAIT), None);
+        assert_eq!(draw(&mut tracker, WORLD), Some(Eye::Right));
+        assert_eq!(draw(&mut tracker, MINIMAP), None);
+        assert_eq!(draw(&mut tracker, WORLD), Some(Eye::Left));
+    }
+
+    #[test]
+    fn other_cameras_can_draw_during_an_eye_pass() {
+        let mut tracker = CameraTracker::new();
+        assert_eq!(tracker.begin(WORLD), Some(Eye::Left));
+        assert_eq!(draw(&mut tracker, MINIMAP), None);
+        assert_eq!(tracker.end(WORLD), Some(Eye::Left));
+        assert_eq!(draw(&mut tracker, WORLD), Some(Eye::Right));
+    }
+
+    #[test]
+    fn resized_camera_is_the_main_one() {
+        let mut tracker = CameraTracker::new();
+        assert_eq!(draw(&mut tracker, MINIMAP), Some(Eye::Left));
+        tracker.set_main(WORLD);
+        assert_eq!(draw(&mut tracker, MINIMAP), None);
+        assert_eq!(draw(&mut tracker, WORLD), Some(Eye::Left));
+        // Resizing the main camera again doesn't restart the frame.
+        tracker.set_main(WORLD);
+        assert_eq!(draw(&mut tracker, WORLD), Some(Eye::Right));
+    }
+
+    #[test]
+    fn a_lost_end_restarts_from_the_left_eye() {
+        let mut tracker = CameraTracker::new();
+        assert_eq!(draw(&mut tracker, WORLD), Some(Eye::Left));
+        assert_eq!(tracker.begin(WORLD), Some(Eye::Right));
+        assert_eq!(tracker.begin(WORLD), Some(Eye::Left));
+        assert_eq!(tracker.end(WORLD), Some(Eye::Left));
+        assert_eq!(draw(&mut tracker, WORLD), Some(Eye::Right));
+    }
+
+    #[test]
+    fn unmatched_ends_are_ignored() {
+        let mut tracker = CameraTracker::new();
+        assert_eq!(tracker.end(WORLD), None);
+        assert_eq!(draw(&mut tracker, WORLD), Some(Eye::Left));
+        assert_eq!(tracker.end(WORLD), None);
+        assert_eq!(tracker.end(MINIMAP), None);
+        assert_eq!(draw(&mut tracker, WORLD), Some(Eye::Right));
+    }
+
+    #[test]
+    fn a_replaced_main_camera_is_taken_over() {
+        const NEW_WORLD: usize = 0x4000;
+        let mut tracker = CameraTracker::new();
+        assert_eq!(draw(&mut tracker, WORLD), Some(Eye::Left));
+        for _ in 1..ADOPT_AFTER {
+            assert_eq!(draw(&mut tracker, NEW_WORLD), None);
+            assert_eq!(draw(&mut tracker, MINIMAP), None);
+        }
+        // One more draw of the old camera, and the count starts over.
+        assert_eq!(draw(&mut tracker, WORLD), Some(Eye::Right));
+        for _ in 1..ADOPT_AFTER {
+            assert_eq!(draw(&mut tracker, NEW_WORLD), None);
+            assert_eq!(draw(&mut tracker, MINIMAP), None);
+        }
+        assert_eq!(draw(&mut tracker, NEW_WORLD), Some(Eye::Left));
+        assert_eq!(draw(&mut tracker, MINIMAP), None);
+        assert_eq!(draw(&mut tracker, WORLD), None);
+        assert_eq!(draw(&mut tracker, NEW_WORLD), Some(Eye::Right));
+    }
+}
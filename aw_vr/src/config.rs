@@ -0,0 +1,585 @@
+//! AW_VR's settings, read from `aw_vr.toml` when it's injected.
+//!
+//! The file is the one the injector names, or else `aw_vr.toml` next to
+//! `aw_vr.dll`. Anything it leaves out keeps its default, and a file that
+//! doesn't parse or has out-of-range values is ignored as a whole. The file is
+//! watched while AW runs, and reloaded when it changes.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use log::LevelFilter;
+use toml;
+
+use backend::{button, TrackingOrigin};
+use bindings::{Binding, Direction, Hand, Input, Mode, Target, VrAction};
+use keyboard::Key;
+
+pub const FILE_NAME: &str = "aw_vr.toml";
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Meters in one RenderWare unit.
+    pub world_scale: f32,
+    /// How high above the floor, in meters, the headset puts you at AW's
+    /// camera. Only used with the floor tracking origin.
+    pub eye_height: f32,
+    pub tracking_origin: TrackingOrigin,
+    /// Scales the eye buffers' size from what the runtime recommends.
+    pub pixel_density: f32,
+    pub locomotion: LocomotionSettings,
+    pub thumbstick: Thumbstick,
+    pub keys: KeyBindings,
+    /// What every controller input does. Without it, the menu button
+    /// recenters, the stick clicks toggle pass-through and jumping, and with
+    /// keys locomotion the thumbsticks press `keys` as `thumbstick` says.
+    pub bindings: Option<Vec<Binding>>,
+    /// Overrides `AW_VR_LOG_LEVEL`.
+    pub log_level: Option<LevelFilter>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            world_scale: 10.0,
+            eye_height: 0.17 * 0.9 * 10.0,
+            tracking_origin: TrackingOrigin::Floor,
+            pixel_density: 1.0,
+            locomotion: LocomotionSettings::default(),
+            thumbstick: Thumbstick::default(),
+            keys: KeyBindings::default(),
+            bindings: None,
+            log_level: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocomotionMode {
+    /// The thumbsticks move as fast as they're pushed.
+    Analog,
+    /// The thumbsticks hold keys, as `Thumbstick` says, or not at all with
+    /// bindings of your own.
+    Keys,
+}
+
+/// Which way the left stick's forward is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocomotionReference {
+    /// The way the avatar faces.
+    Avatar,
+    /// The way the headset faces. The avatar turns to face it while moving.
+    Head,
+    /// The way the left controller points. The avatar turns to face it while
+    /// moving.
+    Controller,
+}
+
+/// How the right stick turns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Turning {
+    /// Steadily, faster the further it's pushed.
+    Smooth,
+    /// By a fixed angle all at once, each time it's pushed.
+    Snap,
+}
+
+/// How the thumbsticks move the avatar.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocomotionSettings {
+    pub mode: LocomotionMode,
+    pub reference: LocomotionReference,
+    /// How far a stick can be pushed, from 0 to 1, before it moves anything.
+    pub deadzone: f32,
+    /// AW's running speed, in walking speeds. A stick pushed all the way runs.
+    pub run_speed: f32,
+    /// Whether the left stick's sideways push strafes.
+    pub strafe: bool,
+    /// Whether pushing the right stick up and down flies. It then only goes
+    /// whichever way it's pushed furthest, turning or flying.
+    pub fly: bool,
+    /// How fast AW turns the avatar, in degrees per second.
+    pub turn_rate: f32,
+    /// How the right stick turns the view. The avatar follows as fast as AW
+    /// turns it, as near as its turn keys get.
+    pub turning: Turning,
+    /// How fast smooth turning turns with the stick pushed all the way, in
+    /// degrees per second.
+    pub turn_speed: f32,
+    /// How far each snap turns, in degrees.
+    pub snap_angle: f32,
+}
+
+impl Default for LocomotionSettings {
+    fn default() -> LocomotionSettings {
+        LocomotionSettings {
+            mode: LocomotionMode::Analog,
+            reference: LocomotionReference::Avatar,
+            deadzone: 0.15,
+            run_speed: 2.0,
+            strafe: true,
+            fly: true,
+            turn_rate: 90.0,
+            turning: Turning::Smooth,
+            turn_speed: 90.0,
+            snap_angle: 45.0,
+        }
+    }
+}
+
+/// How far a thumbstick has to be pushed, from 0 to 1, to press its keys with
+/// keys locomotion.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Thumbstick {
+    /// Walking and turning.
+    pub move_threshold: f32,
+    /// Running as well, by holding the run key.
+    pub run_threshold: f32,
+}
+
+impl Default for Thumbstick {
+    fn default() -> Thumbstick {
+        Thumbstick {
+            move_threshold: 0.5,
+            run_threshold: 0.75,
+        }
+    }
+}
+
+/// The keys the controllers press. Strafing holds `strafe` along with
+/// `turn_left` or `turn_right`.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub forward: Key,
+    pub back: Key,
+    pub turn_left: Key,
+    pub turn_right: Key,
+    pub run: Key,
+    pub strafe: Key,
+    pub fly_up: Key,
+    pub fly_down: Key,
+    /// Held while pass-through is toggled on, to walk through walls.
+    pub pass_through: Key,
+    /// Held while jumping is toggled on.
+    pub jump: Key,
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            forward: Key::Up,
+            back: Key::Down,
+            turn_left: Key::Left,
+            turn_right: Key::Right,
+            run: Key::Control,
+            strafe: Key::Alt,
+            fly_up: Key::Plus,
+            fly_down: Key::Minus,
+            pass_through: Key::Shift,
+            jump: Key::Space,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    /// The file couldn't be read.
+    Read { path: PathBuf, reason: String },
+    /// The file isn't TOML, or doesn't match `Config`.
+    Parse(String),
+    /// A setting is out of range.
+    Invalid { setting: &'static str, reason: &'static str },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Read { ref path, ref reason } => write!(f, "unable to read {}: {}", path.display(), reason),
+            ConfigError::Parse(ref reason) => write!(f, "{}", reason),
+            ConfigError::Invalid { setting, reason } => write!(f, "{} {}", setting, reason),
+        }
+    }
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, ConfigError> {
+        let config: Config = toml::from_str(text).map_err(|error| ConfigError::Parse(error.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|error| ConfigError::Read { path: path.to_owned(), reason: error.to_string() })?;
+        Config::parse(&text)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |setting, reason| Err(ConfigError::Invalid { setting, reason });
+        if !(self.world_scale.is_finite() && self.world_scale > 0.0) {
+            return invalid("world_scale", "must be more than 0");
+        }
+        if !(self.eye_height.is_finite() && self.eye_height >= 0.0) {
+            return invalid("eye_height", "can't be negative");
+        }
+        if !(self.pixel_density.is_finite() && self.pixel_density > 0.0) {
+            return invalid("pixel_density", "must be more than 0");
+        }
+        let LocomotionSettings { deadzone, run_speed, turn_rate, turn_speed, snap_angle, .. } = self.locomotion;
+        if !(0.0..1.0).contains(&deadzone) {
+            return invalid("locomotion.deadzone", "must be at least 0 and less than 1");
+        }
+        if !(run_speed.is_finite() && run_speed >= 1.0) {
+            return invalid("locomotion.run_speed", "must be at least 1");
+        }
+        if !(turn_rate.is_finite() && turn_rate > 0.0) {
+            return invalid("locomotion.turn_rate", "must be more than 0");
+        }
+        if !(turn_speed.is_finite() && turn_speed > 0.0) {
+            return invalid("locomotion.turn_speed", "must be more than 0");
+        }
+        if !(snap_angle > 0.0 && snap_angle <= 180.0) {
+            return invalid("locomotion.snap_angle", "must be more than 0 and at most 180");
+        }
+        let Thumbstick { move_threshold, run_threshold } = self.thumbstick;
+        if !(move_threshold > 0.0 && move_threshold <= 1.0) {
+            return invalid("thumbstick.move_threshold", "must be more than 0 and at most 1");
+        }
+        if !(run_threshold >= move_threshold && run_threshold <= 1.0) {
+            return invalid("thumbstick.run_threshold", "must be at least thumbstick.move_threshold and at most 1");
+        }
+        for binding in self.bindings() {
+            if let Err(reason) = binding.check() {
+                return invalid("bindings", reason);
+            }
+        }
+        Ok(())
+    }
+
+    /// The `locomotion` settings that were changed but do nothing, as they
+    /// only apply to analog locomotion.
+    pub fn ignored_settings(&self) -> Vec<&'static str> {
+        if self.locomotion.mode == LocomotionMode::Analog {
+            return Vec::new();
+        }
+        let (set, default) = (self.locomotion, LocomotionSettings::default());
+        let mut ignored = Vec::new();
+        if set.reference != default.reference {
+            ignored.push("locomotion.reference");
+        }
+        if set.deadzone != default.deadzone {
+            ignored.push("locomotion.deadzone");
+        }
+        if set.run_speed != default.run_speed {
+            ignored.push("locomotion.run_speed");
+        }
+        if set.strafe != default.strafe {
+            ignored.push("locomotion.strafe");
+        }
+        if set.turn_rate != default.turn_rate {
+            ignored.push("locomotion.turn_rate");
+        }
+        if set.turning != default.turning {
+            ignored.push("locomotion.turning");
+        }
+        if set.turn_speed != default.turn_speed {
+            ignored.push("locomotion.turn_speed");
+        }
+        if set.snap_angle != default.snap_angle {
+            ignored.push("locomotion.snap_angle");
+        }
+        ignored
+    }
+
+    /// `bindings`, or the ones `keys` and `thumbstick` make.
+    pub fn bindings(&self) -> Vec<Binding> {
+        if let Some(ref bindings) = self.bindings {
+            return bindings.clone();
+        }
+        let keys = |key| Target::Keys(vec![key]);
+        let toggle = |input, key| Binding { mode: Mode::Toggle, ..Binding::new(Input::Button(input), keys(key), 0.5) };
+        let mut bindings = vec![
+            Binding::new(Input::Button(button::ENTER), Target::Vr(VrAction::Recenter), 0.5),
+            toggle(button::LTHUMB, self.keys.pass_through),
+            toggle(button::RTHUMB, self.keys.jump),
+        ];
+        if self.locomotion.mode == LocomotionMode::Analog {
+            return bindings;
+        }
+        let Thumbstick { move_threshold, run_threshold } = self.thumbstick;
+        let left = |direction| Input::Stick(Hand::Left, direction);
+        let right = |direction| Input::Stick(Hand::Right, direction);
+        bindings.extend(vec![
+            Binding::new(left(Direction::Up), keys(self.keys.forward), move_threshold),
+            Binding::new(left(Direction::Down), keys(self.keys.back), move_threshold),
+            Binding::new(left(Direction::Up), keys(self.keys.run), run_threshold),
+            Binding::new(left(Direction::Down), keys(self.keys.run), run_threshold),
+            Binding::new(right(Direction::Left), keys(self.keys.turn_left), move_threshold),
+            Binding::new(right(Direction::Right), keys(self.keys.turn_right), move_threshold),
+        ]);
+        if self.locomotion.fly {
+            bindings.push(Binding::new(right(Direction::Up), keys(self.keys.fly_up), move_threshold));
+            bindings.push(Binding::new(right(Direction::Down), keys(self.keys.fly_down), move_threshold));
+        }
+        bindings
+    }
+
+    /// Converts meters to RenderWare units.
+    pub fn to_world(&self, meters: f32) -> f32 {
+        meters / self.world_scale
+    }
+}
+
+/// Notices when a config file changes.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    interval: Duration,
+    checked: Instant,
+    /// The file's modification time and size when last loaded, if it existed.
+    version: Option<(SystemTime, u64)>,
+}
+
+impl ConfigWatcher {
+    /// Watches `path`, looking at it at most once per `interval`. Only changes
+    /// made after this is created count.
+    pub fn new(path: PathBuf, interval: Duration) -> ConfigWatcher {
+        let version = file_version(&path);
+        ConfigWatcher { path, interval, checked: Instant::now(), version }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reloads the file if it has changed since it was last looked at. A file
+    /// that has gone away doesn't count as a change.
+    pub fn poll(&mut self) -> Option<Result<Config, ConfigError>> {
+        if self.checked.elapsed() < self.interval {
+            return None;
+        }
+        self.checked = Instant::now();
+        let version = file_version(&self.path);
+        if version.is_none() || version == self.version {
+            return None;
+        }
+        self.version = version;
+        Some(Config::load(&self.path))
+    }
+}
+
+fn file_version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Where to read the config from: the path the injector passed, if any, or
+/// `aw_vr.toml` next to the DLL. `None` if there's no config to read.
+pub fn locate(passed: Option<PathBuf>) -> Option<PathBuf> {
+    passed.or_else(|| module_dir().map(|dir| dir.join(FILE_NAME)).filter(|path| path.is_file()))
+}
+
+/// The directory `aw_vr.dll` was loaded from.
+#[cfg(windows)]
+fn module_dir() -> Option<PathBuf> {
+    use std::ffi::OsString;
+    use std::os::raw::c_void;
+    use std::os::windows::ffi::OsStringExt;
+    use std::ptr;
+
+    const GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT: u32 = 0x2;
+    const GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS: u32 = 0x4;
+
+    extern "system" {
+        fn GetModuleHandleExW(flags: u32, module_name: *const c_void, module: *mut *mut c_void) -> i32;
+        fn GetModuleFileNameW(module: *mut c_void, filename: *mut u16, size: u32) -> u32;
+    }
+
+    unsafe {
+        let mut module = ptr::null_mut();
+        let flags = GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT;
+        if GetModuleHandleExW(flags, module_dir as *const c_void, &mut module) == 0 {
+            return None;
+        }
+        let mut filename = vec![0u16; 1024];
+        let length = GetModuleFileNameW(module, filename.as_mut_ptr(), filename.len() as u32) as usize;
+        if length == 0 || length >= filename.len() {
+            return None;
+        }
+        PathBuf::from(OsString::from_wide(&filename[..length])).parent().map(Path::to_owned)
+    }
+}
+
+#[cfg(not(windows))]
+fn module_dir() -> Option<PathBuf> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_empty_file_is_all_defaults() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn settings_override_their_defaults() {
+        let config = Config::parse(r#"
+            world_scale = 5.0
+            tracking_origin = "eye"
+
+            [locomotion]
+            mode = "keys"
+            reference = "head"
+            strafe = false
+            turning = "snap"
+            snap_angle = 30.0
+
+            [thumbstick]
+            run_threshold = 0.9
+
+            [keys]
+            run = "shift"
+            forward = "w"
+        "#).unwrap();
+        assert_eq!(config.world_scale, 5.0);
+        assert_eq!(config.eye_height, Config::default().eye_height);
+        assert_eq!(config.tracking_origin, TrackingOrigin::Eye);
+        assert_eq!(config.locomotion, LocomotionSettings {
+            mode: LocomotionMode::Keys,
+            reference: LocomotionReference::Head,
+            strafe: false,
+            turning: Turning::Snap,
+            snap_angle: 30.0,
+            ..LocomotionSettings::default()
+        });
+        assert_eq!(config.thumbstick, Thumbstick { move_threshold: 0.5, run_threshold: 0.9 });
+        assert_eq!(config.keys.run, Key::Shift);
+        assert_eq!(config.keys.forward, Key::Char('w'));
+        assert_eq!(config.keys.back, Key::Down);
+        assert_eq!(config.to_world(10.0), 2.0);
+    }
+
+    #[test]
+    fn mistakes_are_parse_errors() {
+        for text in &["world_scale = \"big\"", "wolrd_scale = 5.0", "[keys]\nrun = \"hyper\"", "tracking_origin = \"ceiling\""] {
+            match Config::parse(text) {
+                Err(ConfigError::Parse(_)) => {},
+                result => panic!("{:?} gave {:?}", text, result),
+            }
+        }
+    }
+
+    #[test]
+    fn bindings_replace_the_thumbstick_keys() {
+        use bindings::InputMapper;
+        use backend::InputState;
+        use keyboard::Mouse;
+
+        let mut keys = Config::default();
+        keys.locomotion.mode = LocomotionMode::Keys;
+        let mut defaults = InputMapper::new(keys.bindings());
+        let output = defaults.update(&InputState { thumbstick: [[0.0, 0.9], [-0.6, 0.0]], ..InputState::default() });
+        assert_eq!(output.keys, vec![Key::Up, Key::Control, Key::Left]);
+        assert_eq!(Config::default().bindings().len(), 3);
+        let click = InputState { buttons: button::LTHUMB, ..InputState::default() };
+        assert_eq!(defaults.update(&click).keys, vec![Key::Shift]);
+        assert_eq!(defaults.update(&InputState::default()).keys, vec![Key::Shift]);
+        defaults.update(&click);
+        assert_eq!(defaults.update(&InputState::default()).keys, vec![]);
+
+        let config = Config::parse(r#"
+            [[bindings]]
+            input = "right_trigger"
+            mouse = "left"
+            threshold = 0.8
+
+            [[bindings]]
+            input = "a"
+            keys = ["control", "c"]
+            mode = "toggle"
+        "#).unwrap();
+        let bindings = config.bindings();
+        assert_eq!(bindings.len(), 2);
+        assert_eq!(bindings[0].target, Target::Mouse(Mouse::Left));
+        assert_eq!((bindings[0].threshold, bindings[0].release), (0.8, 0.75));
+        assert_eq!(bindings[1].target, Target::Keys(vec![Key::Control, Key::Char('c')]));
+        assert_eq!(bindings[1].mode, Mode::Toggle);
+    }
+
+    #[test]
+    fn bindings_need_one_target() {
+        for text in &["[[bindings]]\ninput = \"a\"", "[[bindings]]\ninput = \"a\"\nkeys = [\"space\"]\naction = \"recenter\"", "[[bindings]]\ninput = \"nose\"\nmouse = \"left\""] {
+            match Config::parse(text) {
+                Err(ConfigError::Parse(_)) => {},
+                result => panic!("{:?} gave {:?}", text, result),
+            }
+        }
+    }
+
+    #[test]
+    fn log_level_is_optional() {
+        assert_eq!(Config::parse("log_level = \"debug\"").unwrap().log_level, Some(LevelFilter::Debug));
+        assert_eq!(Config::default().log_level, None);
+    }
+
+    #[test]
+    fn watcher_reloads_changed_files() {
+        use std::env;
+        use std::process;
+
+        let path = env::temp_dir().join(format!("aw_vr_watch_{}.toml", process::id()));
+        fs::write(&path, "world_scale = 5.0").unwrap();
+        let mut watcher = ConfigWatcher::new(path.clone(), Duration::from_secs(0));
+        assert_eq!(watcher.poll(), None);
+
+        fs::write(&path, "world_scale = 200.0").unwrap();
+        assert_eq!(watcher.poll().unwrap().unwrap().world_scale, 200.0);
+        assert_eq!(watcher.poll(), None);
+
+        fs::write(&path, "world_scale = -1").unwrap();
+        match watcher.poll() {
+            Some(Err(ConfigError::Parse(_))) | Some(Err(ConfigError::Invalid { .. })) => {},
+            result => panic!("invalid config gave {:?}", result),
+        }
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(watcher.poll(), None);
+    }
+
+    #[test]
+    fn analog_settings_are_flagged_in_keys_mode() {
+        let text = "[locomotion]\nturning = \"snap\"\nsnap_angle = 30.0\nfly = false";
+        assert_eq!(Config::parse(text).unwrap().ignored_settings(), Vec::<&str>::new());
+        let keys = Config::parse(&format!("{}\nmode = \"keys\"", text)).unwrap();
+        assert_eq!(keys.ignored_settings(), vec!["locomotion.turning", "locomotion.snap_angle"]);
+    }
+
+    #[test]
+    fn out_of_range_settings_are_rejected() {
+        let setting = |text| match Config::parse(text) {
+            Err(ConfigError::Invalid { setting, .. }) => setting,
+            result => panic!("{:?} gave {:?}", text, result),
+        };
+        assert_eq!(setting("world_scale = 0.0"), "world_scale");
+        assert_eq!(setting("eye_height = -1.0"), "eye_height");
+        assert_eq!(setting("pixel_density = 0.0"), "pixel_density");
+        assert_eq!(setting("[thumbstick]\nmove_threshold = 1.5"), "thumbstick.move_threshold");
+        assert_eq!(setting("[thumbstick]\nmove_threshold = 0.8\nrun_threshold = 0.6"), "thumbstick.run_threshold");
+        assert_eq!(setting("[locomotion]\ndeadzone = 1.0"), "locomotion.deadzone");
+        assert_eq!(setting("[locomotion]\nrun_speed = 0.5"), "locomotion.run_speed");
+        assert_eq!(setting("[locomotion]\nturn_rate = 0.0"), "locomotion.turn_rate");
+        assert_eq!(setting("[locomotion]\nturn_speed = -45.0"), "locomotion.turn_speed");
+        assert_eq!(setting("[locomotion]\nsnap_angle = 270.0"), "locomotion.snap_angle");
+        assert_eq!(setting("[[bindings]]\ninput = \"a\"\nkeys = [\"space\"]\nthreshold = 0.5\nrelease = 0.7"), "bindings");
+    }
+}
@@ -0,0 +1,96 @@
+//! Taking AW_VR back out of AW, when the injector asks.
+//!
+//! The DLL makes a named event for its process and a thread that waits on it.
+//! Once the injector sets the event, the thread has VR turned off and the
+//! hooks removed, then unloads the DLL it's running in. If that can't be done
+//! yet, the event is reset and the thread goes back to waiting, so the
+//! injector can ask again.
+
+#[cfg(windows)]
+use std::os::raw::c_void;
+
+/// Starts the thread waiting for the injector. Each time it's asked, it calls
+/// `eject`, and unloads the DLL once that says it's safe to.
+#[cfg(windows)]
+pub fn listen(eject: fn() -> bool) -> Result<(), String> {
+    use std::ffi::OsStr;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::process;
+    use std::ptr;
+
+    use aw_vr_payload;
+
+    let name: Vec<u16> = OsStr::new(&aw_vr_payload::eject_event_name(process::id())).encode_wide().chain(Some(0)).collect();
+    unsafe {
+        let event = CreateEventW(ptr::null_mut(), 1, 0, name.as_ptr());
+        if event.is_null() {
+            return Err(format!("unable to create the eject event: {}", io::Error::last_os_error()));
+        }
+        let listener = Box::into_raw(Box::new(Listener { event, eject }));
+        let thread = CreateThread(ptr::null_mut(), 0, wait_for_eject, listener as *mut c_void, 0, ptr::null_mut());
+        if thread.is_null() {
+            let error = io::Error::last_os_error();
+            drop(Box::from_raw(listener));
+            CloseHandle(event);
+            return Err(format!("unable to start the eject thread: {}", error));
+        }
+        CloseHandle(thread);
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn listen(_eject: fn() -> bool) -> Result<(), String> {
+    Err("ejecting needs Windows".to_owned())
+}
+
+#[cfg(windows)]
+struct Listener {
+    event: *mut c_void,
+    eject: fn() -> bool,
+}
+
+#[cfg(windows)]
+extern "system" {
+    fn CreateEventW(attributes: *mut c_void, manual_reset: i32, initial_state: i32, name: *const u16) -> *mut c_void;
+    fn CreateThread(attributes: *mut c_void, stack_size: usize, start: extern "system" fn(*mut c_void) -> u32, parameter: *mut c_void, flags: u32, thread_id: *mut u32) -> *mut c_void;
+    fn WaitForSingleObject(handle: *mut c_void, milliseconds: u32) -> u32;
+    fn ResetEvent(event: *mut c_void) -> i32;
+    fn CloseHandle(handle: *mut c_void) -> i32;
+    fn GetModuleHandleExW(flags: u32, module_name: *const c_void, module: *mut *mut c_void) -> i32;
+    fn FreeLibraryAndExitThread(module: *mut c_void, exit_code: u32) -> !;
+}
+
+#[cfg(windows)]
+extern "system" fn wait_for_eject(listener: *mut c_void) -> u32 {
+    const INFINITE: u32 = 0xFFFF_FFFF;
+    const WAIT_OBJECT_0: u32 = 0;
+    const GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT: u32 = 0x2;
+    const GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS: u32 = 0x4;
+
+    let listener = unsafe { Box::from_raw(listener as *mut Listener) };
+    unsafe {
+        loop {
+            if WaitForSingleObject(listener.event, INFINITE) != WAIT_OBJECT_0 {
+                CloseHandle(listener.event);
+                return 1;
+            }
+            if (listener.eject)() {
+                break;
+            }
+            // The event stays, so the injector still finds AW_VR to try again.
+            ResetEvent(listener.event);
+        }
+        // EasyHook's reference is the only one, so this unloads the DLL.
+        let mut module = std::ptr::null_mut();
+        let flags = GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT;
+        let found = GetModuleHandleExW(flags, wait_for_eject as *const c_void, &mut module) != 0;
+        CloseHandle(listener.event);
+        if !found {
+            return 1;
+        }
+        drop(listener);
+        FreeLibraryAndExitThread(module, 0)
+    }
+}
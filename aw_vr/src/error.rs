@@ -0,0 +1,80 @@
+//! Everything that can go wrong inside AW_VR's hooks.
+//!
+//! None of it may unwind into Active Worlds: a hook that fails turns VR off and
+//! from then on just passes calls through to RenderWare.
+
+use std::any::Any;
+use std::fmt;
+
+use backend;
+use easyhook::EasyHookError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AwVrError {
+    /// A library AW_VR calls into couldn't be loaded.
+    Library { library: &'static str, reason: String },
+    /// A library lacks a function AW_VR needs.
+    Symbol { library: &'static str, symbol: &'static str },
+    /// EasyHook couldn't hook a RenderWare function.
+    Hook { function: &'static str, error: EasyHookError },
+    /// The headset runtime failed.
+    Vr(backend::Error),
+    /// GL reported an error after AW_VR did something.
+    Gl { code: i32, during: &'static str },
+    /// The runtime handed out texture 0 for an eye.
+    NoTexture,
+    /// The eye buffer couldn't be made into a complete framebuffer.
+    IncompleteFramebuffer { width: u32, height: u32, status: i32 },
+    /// Code inside a hook panicked.
+    Panic(String),
+}
+
+impl AwVrError {
+    /// Turns a caught panic's payload into an error.
+    pub fn from_panic(payload: Box<dyn Any + Send>) -> AwVrError {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(message) => (*message).to_owned(),
+                Err(_) => "unknown panic".to_owned(),
+            },
+        };
+        AwVrError::Panic(message)
+    }
+}
+
+impl From<backend::Error> for AwVrError {
+    fn from(error: backend::Error) -> AwVrError {
+        AwVrError::Vr(error)
+    }
+}
+
+impl fmt::Display for AwVrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AwVrError::Library { library, ref reason } => write!(f, "unable to load {}: {}", library, reason),
+            AwVrError::Symbol { library, symbol } => write!(f, "{} has no {}", library, symbol),
+            AwVrError::Hook { function, ref error } => write!(f, "unable to hook {}: {}", function, error),
+            AwVrError::Vr(ref error) => write!(f, "VR error: {}", error),
+            AwVrError::Gl { code, during } => write!(f, "GL error 0x{:X} while {}", code, during),
+            AwVrError::NoTexture => write!(f, "the runtime gave an eye texture 0"),
+            AwVrError::IncompleteFramebuffer { width, height, status } => write!(f, "eye buffer incomplete at {}x{} (status 0x{:X})", width, height, status),
+            AwVrError::Panic(ref message) => write!(f, "panicked: {}", message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+
+    use super::*;
+
+    #[test]
+    fn panics_keep_their_message() {
+        let payload = panic::catch_unwind(|| panic!("eye {} lost", 1)).unwrap_err();
+        assert_eq!(AwVrError::from_panic(payload), AwVrError::Panic("eye 1 lost".to_owned()));
+        let payload = panic::catch_unwind(|| panic!("static")).unwrap_err();
+        assert_eq!(AwVrError::from_panic(payload), AwVrError::Panic("static".to_owned()));
+    }
+}
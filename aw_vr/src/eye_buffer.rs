@@ -0,0 +1,108 @@
+//! The offscreen framebuffer RenderWare draws the eyes into, so they can be
+//! rendered at the headset's resolution instead of the AW window's.
+
+use backend::EyeTextures;
+use error::AwVrError;
+use gl::{self, *};
+
+pub struct EyeBuffer {
+    framebuffer: u32,
+    color: u32,
+    depth: u32,
+    size: (u32, u32),
+    bound: bool,
+}
+
+impl EyeBuffer {
+    pub fn new() -> EyeBuffer {
+        EyeBuffer {
+            framebuffer: 0,
+            color: 0,
+            depth: 0,
+            size: (0, 0),
+            bound: false,
+        }
+    }
+
+    /// Whether drawing currently goes to the eye buffer rather than the window.
+    pub fn is_bound(&self) -> bool {
+        self.bound
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Sends drawing to the eye buffer, (re)allocating it at `size` first.
+    pub fn bind(&mut self, size: (u32, u32)) -> Result<(), AwVrError> {
+        let fns = gl::framebuffers()?;
+        if self.framebuffer == 0 {
+            (fns.glGenFramebuffers)(1, &mut self.framebuffer);
+            (fns.glGenRenderbuffers)(1, &mut self.color);
+            (fns.glGenRenderbuffers)(1, &mut self.depth);
+        }
+        (fns.glBindFramebuffer)(GL_FRAMEBUFFER, self.framebuffer);
+        if self.size != size {
+            let (width, height) = size;
+            (fns.glBindRenderbuffer)(GL_RENDERBUFFER, self.color);
+            (fns.glRenderbufferStorage)(GL_RENDERBUFFER, GL_RGBA8, width, height);
+            (fns.glFramebufferRenderbuffer)(GL_FRAMEBUFFER, GL_COLOR_ATTACHMENT0, GL_RENDERBUFFER, self.color);
+            (fns.glBindRenderbuffer)(GL_RENDERBUFFER, self.depth);
+            (fns.glRenderbufferStorage)(GL_RENDERBUFFER, GL_DEPTH24_STENCIL8, width, height);
+            (fns.glFramebufferRenderbuffer)(GL_FRAMEBUFFER, GL_DEPTH_STENCIL_ATTACHMENT, GL_RENDERBUFFER, self.depth);
+            (fns.glBindRenderbuffer)(GL_RENDERBUFFER, 0);
+            let status = (fns.glCheckFramebufferStatus)(GL_FRAMEBUFFER);
+            if status != GL_FRAMEBUFFER_COMPLETE {
+                (fns.glBindFramebuffer)(GL_FRAMEBUFFER, 0);
+                self.size = (0, 0);
+                return Err(AwVrError::IncompleteFramebuffer { width, height, status });
+            }
+            self.size = size;
+        }
+        self.bound = true;
+        Ok(())
+    }
+
+    /// Copies what was drawn into `textures`, which must be at least as large.
+    /// Depth textures take their copy from the depth buffer.
+    pub fn copy_to(&self, textures: EyeTextures) -> Result<(), AwVrError> {
+        let gl = gl::functions()?;
+        let (width, height) = self.size;
+        (gl.glEnable)(GL_TEXTURE_2D);
+        (gl.glReadBuffer)(GL_COLOR_ATTACHMENT0);
+        for &texture in Some(textures.color).iter().chain(textures.depth.iter()) {
+            (gl.glBindTexture)(GL_TEXTURE_2D, texture);
+            (gl.glCopyTexSubImage2D)(GL_TEXTURE_2D, 0, 0, 0, 0, 0, width, height);
+        }
+        (gl.glBindTexture)(GL_TEXTURE_2D, 0);
+        (gl.glDisable)(GL_TEXTURE_2D);
+        Ok(())
+    }
+
+    /// Scales what was drawn onto the window, and sends drawing back there.
+    pub fn unbind(&mut self, window: (u32, u32)) -> Result<(), AwVrError> {
+        let gl = gl::functions()?;
+        let fns = gl::framebuffers()?;
+        let (width, height) = self.size;
+        (fns.glBindFramebuffer)(GL_READ_FRAMEBUFFER, self.framebuffer);
+        (fns.glBindFramebuffer)(GL_DRAW_FRAMEBUFFER, 0);
+        (fns.glBlitFramebuffer)(0, 0, width as i32, height as i32, 0, 0, window.0 as i32, window.1 as i32, GL_COLOR_BUFFER_BIT, GL_LINEAR);
+        (fns.glBindFramebuffer)(GL_FRAMEBUFFER, 0);
+        (gl.glReadBuffer)(GL_BACK);
+        self.bound = false;
+        Ok(())
+    }
+}
+
+impl Drop for EyeBuffer {
+    fn drop(&mut self) {
+        if self.framebuffer == 0 {
+            return;
+        }
+        if let Ok(fns) = gl::framebuffers() {
+            (fns.glDeleteFramebuffers)(1, &self.framebuffer);
+            (fns.glDeleteRenderbuffers)(1, &self.color);
+            (fns.glDeleteRenderbuffers)(1, &self.depth);
+        }
+    }
+}
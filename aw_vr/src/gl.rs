@@ -0,0 +1,143 @@
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+use error::AwVrError;
+use lib;
+
+lazy_static! {
+    static ref GL: Result<lib::Library, AwVrError> = lib::Library::new("OPENGL32")
+        .map_err(|error| AwVrError::Library { library: "OPENGL32", reason: error.to_string() });
+    static ref Functions: Result<GlFunctions, AwVrError> = GlFunctions::load();
+    /// Resolved on first use, which must be on a thread with AW's context current.
+    static ref Framebuffers: Result<FramebufferFunctions, AwVrError> = FramebufferFunctions::load();
+}
+
+pub const GL_TEXTURE_2D: i32 = 0x0DE1;
+pub const GL_VIEWPORT: i32 = 0x0BA2;
+pub const GL_BACK: i32 = 0x0404;
+pub const GL_LINEAR: i32 = 0x2601;
+pub const GL_COLOR_BUFFER_BIT: u32 = 0x4000;
+pub const GL_RGBA8: i32 = 0x8058;
+pub const GL_DEPTH24_STENCIL8: i32 = 0x88F0;
+pub const GL_DEPTH_STENCIL_ATTACHMENT: i32 = 0x821A;
+pub const GL_READ_FRAMEBUFFER: i32 = 0x8CA8;
+pub const GL_DRAW_FRAMEBUFFER: i32 = 0x8CA9;
+pub const GL_FRAMEBUFFER_COMPLETE: i32 = 0x8CD5;
+pub const GL_COLOR_ATTACHMENT0: i32 = 0x8CE0;
+pub const GL_FRAMEBUFFER: i32 = 0x8D40;
+pub const GL_RENDERBUFFER: i32 = 0x8D41;
+
+/// What AW_VR needs from OPENGL32 itself.
+#[allow(non_snake_case)]
+pub struct GlFunctions {
+    pub glGetIntegerv: extern "system" fn(i32, *mut i32),
+    pub glBindTexture: extern "system" fn(i32, u32),
+    pub glReadBuffer: extern "system" fn(i32),
+    pub glCopyTexSubImage2D: extern "system" fn(i32, i32, i32, i32, i32, i32, u32, u32),
+    pub glEnable: extern "system" fn(i32),
+    pub glDisable: extern "system" fn(i32),
+    pub glGetError: extern "system" fn() -> i32,
+    wglGetCurrentDC: extern "system" fn() -> *mut c_void,
+    wglGetCurrentContext: extern "system" fn() -> *mut c_void,
+    wglGetProcAddress: extern "system" fn(*const c_char) -> *const c_void,
+}
+
+/// `name` must be NUL-terminated, and `T` the function's type.
+unsafe fn symbol<T: Copy>(library: &lib::Library, name: &'static str) -> Result<T, AwVrError> {
+    library.get::<T>(name.as_bytes())
+        .map(|symbol| *symbol)
+        .map_err(|_| AwVrError::Symbol { library: "OPENGL32", symbol: &name[..name.len() - 1] })
+}
+
+impl GlFunctions {
+    fn load() -> Result<GlFunctions, AwVrError> {
+        let library = GL.as_ref().map_err(Clone::clone)?;
+        unsafe {
+            Ok(GlFunctions {
+                glGetIntegerv: symbol(library, "glGetIntegerv\0")?,
+                glBindTexture: symbol(library, "glBindTexture\0")?,
+                glReadBuffer: symbol(library, "glReadBuffer\0")?,
+                glCopyTexSubImage2D: symbol(library, "glCopyTexSubImage2D\0")?,
+                glEnable: symbol(library, "glEnable\0")?,
+                glDisable: symbol(library, "glDisable\0")?,
+                glGetError: symbol(library, "glGetError\0")?,
+                wglGetCurrentDC: symbol(library, "wglGetCurrentDC\0")?,
+                wglGetCurrentContext: symbol(library, "wglGetCurrentContext\0")?,
+                wglGetProcAddress: symbol(library, "wglGetProcAddress\0")?,
+            })
+        }
+    }
+
+    /// Fails with the last GL error, if there is one, saying what was being done.
+    pub fn check_error(&self, during: &'static str) -> Result<(), AwVrError> {
+        match (self.glGetError)() {
+            0 => Ok(()),
+            code => Err(AwVrError::Gl { code, during }),
+        }
+    }
+}
+
+/// OPENGL32's functions, loading them on first use.
+pub fn functions() -> Result<&'static GlFunctions, AwVrError> {
+    Functions.as_ref().map_err(Clone::clone)
+}
+
+/// The framebuffer functions, looking them up on first use.
+pub fn framebuffers() -> Result<&'static FramebufferFunctions, AwVrError> {
+    Framebuffers.as_ref().map_err(Clone::clone)
+}
+
+/// Framebuffer objects are GL 3.0, so OPENGL32 doesn't export them and they
+/// have to be looked up through the driver.
+#[allow(non_snake_case)]
+pub struct FramebufferFunctions {
+    pub glGenFramebuffers: extern "system" fn(i32, *mut u32),
+    pub glDeleteFramebuffers: extern "system" fn(i32, *const u32),
+    pub glBindFramebuffer: extern "system" fn(i32, u32),
+    pub glCheckFramebufferStatus: extern "system" fn(i32) -> i32,
+    pub glFramebufferRenderbuffer: extern "system" fn(i32, i32, i32, u32),
+    pub glBlitFramebuffer: extern "system" fn(i32, i32, i32, i32, i32, i32, i32, i32, u32, i32),
+    pub glGenRenderbuffers: extern "system" fn(i32, *mut u32),
+    pub glDeleteRenderbuffers: extern "system" fn(i32, *const u32),
+    pub glBindRenderbuffer: extern "system" fn(i32, u32),
+    pub glRenderbufferStorage: extern "system" fn(i32, i32, u32, u32),
+}
+
+/// `name` must be NUL-terminated, and `T` the function's type.
+fn proc_address<T: Copy>(gl: &GlFunctions, name: &'static str) -> Result<T, AwVrError> {
+    let function = (gl.wglGetProcAddress)(name.as_ptr() as *const c_char);
+    if function.is_null() {
+        return Err(AwVrError::Symbol { library: "the GL driver", symbol: &name[..name.len() - 1] });
+    }
+    Ok(unsafe { mem::transmute_copy(&function) })
+}
+
+impl FramebufferFunctions {
+    fn load() -> Result<FramebufferFunctions, AwVrError> {
+        let gl = functions()?;
+        Ok(FramebufferFunctions {
+            glGenFramebuffers: proc_address(gl, "glGenFramebuffers\0")?,
+            glDeleteFramebuffers: proc_address(gl, "glDeleteFramebuffers\0")?,
+            glBindFramebuffer: proc_address(gl, "glBindFramebuffer\0")?,
+            glCheckFramebufferStatus: proc_address(gl, "glCheckFramebufferStatus\0")?,
+            glFramebufferRenderbuffer: proc_address(gl, "glFramebufferRenderbuffer\0")?,
+            glBlitFramebuffer: proc_address(gl, "glBlitFramebuffer\0")?,
+            glGenRenderbuffers: proc_address(gl, "glGenRenderbuffers\0")?,
+            glDeleteRenderbuffers: proc_address(gl, "glDeleteRenderbuffers\0")?,
+            glBindRenderbuffer: proc_address(gl, "glBindRenderbuffer\0")?,
+            glRenderbufferStorage: proc_address(gl, "glRenderbufferStorage\0")?,
+        })
+    }
+}
+
+/// The device context and GL context current on the calling thread, as needed
+/// by runtimes that share AW's context (OpenXR's `XR_KHR_opengl_enable`).
+pub fn current_context() -> Result<(*mut c_void, *mut c_void), AwVrError> {
+    if cfg!(windows) {
+        let gl = functions()?;
+        Ok(((gl.wglGetCurrentDC)(), (gl.wglGetCurrentContext)()))
+    } else {
+        Ok((ptr::null_mut(), ptr::null_mut()))
+    }
+}
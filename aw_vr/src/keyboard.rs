@@ -0,0 +1,220 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(windows)]
+use enigo::{self, Enigo, KeyboardControllable, MouseControllable};
+use serde::de::{self, Deserialize, Deserializer};
+
+/// The keys AW_VR presses on behalf of the controllers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Control,
+    Shift,
+    Alt,
+    Left,
+    Right,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Space,
+    Plus,
+    Minus,
+    /// The key that types this character in the current layout.
+    Char(char),
+}
+
+#[cfg(windows)]
+impl Key {
+    fn enigo(self) -> enigo::Key {
+        match self {
+            Key::Control => enigo::Key::Control,
+            Key::Shift => enigo::Key::Shift,
+            Key::Alt => enigo::Key::Alt,
+            Key::Left => enigo::Key::LeftArrow,
+            Key::Right => enigo::Key::RightArrow,
+            Key::Up => enigo::Key::UpArrow,
+            Key::Down => enigo::Key::DownArrow,
+            Key::PageUp => enigo::Key::PageUp,
+            Key::PageDown => enigo::Key::PageDown,
+            Key::Home => enigo::Key::Home,
+            Key::End => enigo::Key::End,
+            Key::Space => enigo::Key::Space,
+            Key::Plus => enigo::Key::Layout('+'),
+            Key::Minus => enigo::Key::Layout('-'),
+            Key::Char(c) => enigo::Key::Layout(c),
+        }
+    }
+}
+
+/// Keys are named in the config as `control`, `page_up` and so on, or as the
+/// single character they type.
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Key, String> {
+        Ok(match &*name.to_lowercase() {
+            "control" | "ctrl" => Key::Control,
+            "shift" => Key::Shift,
+            "alt" => Key::Alt,
+            "left" => Key::Left,
+            "right" => Key::Right,
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "page_up" => Key::PageUp,
+            "page_down" => Key::PageDown,
+            "home" => Key::Home,
+            "end" => Key::End,
+            "space" => Key::Space,
+            "plus" => Key::Plus,
+            "minus" => Key::Minus,
+            _ => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Key::Char(c),
+                    _ => return Err(format!("unknown key {:?}", name)),
+                }
+            },
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Key, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Key::Char(c) => write!(f, "{}", c),
+            key => write!(f, "{:?}", key),
+        }
+    }
+}
+
+/// What AW_VR can do with the mouse on behalf of the controllers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mouse {
+    Left,
+    Right,
+    Middle,
+    /// One notch of the wheel, rather than a button to hold.
+    WheelUp,
+    WheelDown,
+}
+
+impl Mouse {
+    pub fn is_wheel(self) -> bool {
+        self == Mouse::WheelUp || self == Mouse::WheelDown
+    }
+
+    /// The button, unless this is the wheel.
+    #[cfg(windows)]
+    fn enigo(self) -> Option<enigo::MouseButton> {
+        match self {
+            Mouse::Left => Some(enigo::MouseButton::Left),
+            Mouse::Right => Some(enigo::MouseButton::Right),
+            Mouse::Middle => Some(enigo::MouseButton::Middle),
+            Mouse::WheelUp | Mouse::WheelDown => None,
+        }
+    }
+}
+
+/// The keyboard and mouse, as AW sees them.
+#[derive(Debug)]
+pub struct Keyboard {
+    #[cfg(windows)]
+    enigo: Enigo,
+    /// The keys AW_VR is holding down, in the order they were pressed.
+    held: Vec<Key>,
+    /// Likewise for mouse buttons.
+    held_buttons: Vec<Mouse>,
+}
+
+impl Keyboard {
+    pub fn new() -> Self {
+        Keyboard {
+            #[cfg(windows)]
+            enigo: Enigo::new(),
+            held: Vec::new(),
+            held_buttons: Vec::new(),
+        }
+    }
+
+    pub fn hold(&mut self, key: Key) {
+        if !self.held.contains(&key) {
+            #[cfg(windows)]
+            self.enigo.key_down(key.enigo());
+            self.held.push(key);
+        }
+    }
+
+    pub fn release(&mut self, key: Key) {
+        if let Some(index) = self.held.iter().position(|&held| held == key) {
+            #[cfg(windows)]
+            self.enigo.key_up(key.enigo());
+            self.held.remove(index);
+        }
+    }
+
+    /// Holds exactly `keys`: lets go of the others, most recently pressed
+    /// first, then presses the new ones in order.
+    pub fn hold_only(&mut self, keys: &[Key]) {
+        let released: Vec<Key> = self.held.iter().rev().filter(|key| !keys.contains(key)).cloned().collect();
+        for key in released {
+            self.release(key);
+        }
+        for &key in keys {
+            self.hold(key);
+        }
+    }
+
+    /// Like `hold_only`, for mouse buttons. Wheel notches aren't held.
+    pub fn hold_only_buttons(&mut self, buttons: &[Mouse]) {
+        let released: Vec<Mouse> = self.held_buttons.iter().filter(|button| !buttons.contains(button)).cloned().collect();
+        for button in released {
+            #[cfg(windows)]
+            if let Some(button) = button.enigo() {
+                self.enigo.mouse_up(button);
+            }
+            self.held_buttons.retain(|&held| held != button);
+        }
+        for &button in buttons.iter().filter(|button| !button.is_wheel()) {
+            if !self.held_buttons.contains(&button) {
+                #[cfg(windows)]
+                if let Some(button) = button.enigo() {
+                    self.enigo.mouse_down(button);
+                }
+                self.held_buttons.push(button);
+            }
+        }
+    }
+
+    /// Turns the mouse wheel one notch.
+    pub fn scroll(&mut self, wheel: Mouse) {
+        let notches = match wheel {
+            Mouse::WheelUp => 1,
+            Mouse::WheelDown => -1,
+            _ => return,
+        };
+        #[cfg(windows)]
+        self.enigo.mouse_scroll_y(notches);
+        #[cfg(not(windows))]
+        let _ = notches;
+    }
+
+    /// Lets go of every key and button still held, most recently pressed
+    /// first.
+    pub fn release_all(&mut self) {
+        while let Some(&key) = self.held.last() {
+            self.release(key);
+        }
+        self.hold_only_buttons(&[]);
+    }
+}
@@ -1,361 +1,569 @@
 #![crate_type="cdylib"]
+#![allow(non_upper_case_globals)]
 
+extern crate aw_vr_payload;
+#[macro_use]
 extern crate easyhook;
 extern crate libloading as lib;
 extern crate ovr_sys as vr;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
+#[cfg(windows)]
 extern crate enigo;
 
 #[macro_use]
 extern crate lazy_static;
-
-use std::io::Write;
-use std::os::raw::{c_ulong, c_void};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use easyhook::{lh_install_hook};
-use easyhook::error_string;
-use std::sync::Mutex;
-use std::mem;
-use enigo::{Enigo, Key, KeyboardControllable};
+#[macro_use]
+extern crate log;
+
+mod backend;
+mod bindings;
+mod cameras;
+mod config;
+mod eject;
+mod error;
+mod eye_buffer;
+mod gl;
+mod keyboard;
+mod locomotion;
+mod logging;
+mod rw;
+mod stereo;
+mod timing;
+
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+use easyhook::{Acl, HookRegistry, StaticHook};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use aw_vr_payload::{feature, ConfigSource, InjectionPayload};
+use backend::{Eye, Fov, InputState, Pose, TrackingOrigin, VrBackend};
+use bindings::{InputMapper, VrAction};
+use config::{Config, ConfigWatcher, LocomotionMode};
+use error::AwVrError;
+use eye_buffer::EyeBuffer;
+use gl::*;
+use keyboard::Keyboard;
+use locomotion::{Frame, Locomotion};
+use rw::*;
+use stereo::Stereo;
 
 #[derive(Debug)]
-struct Session(vr::ovrSession);
+struct Matrix(*mut c_void);
 
-unsafe impl Sync for Session {}
+unsafe impl Send for Matrix {}
 
-impl std::ops::Deref for Session {
-    type Target = vr::ovrSession;
+impl std::ops::Deref for Matrix {
+    type Target = *mut c_void;
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-impl std::ops::DerefMut for Session {
+impl std::ops::DerefMut for Matrix {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.0
     }
 }
 
-#[derive(Debug)]
-struct TextureSwapChain(vr::ovrTextureSwapChain);
+/// Set once a hook has failed. VR stays off from then on, and the hooks only
+/// pass calls through to RenderWare.
+static VRDisabled: AtomicBool = AtomicBool::new(false);
 
-unsafe impl Send for TextureSwapChain {}
-unsafe impl Sync for TextureSwapChain {}
+/// Whether the hooks have been limited to AW's render thread.
+static VRHooksPinned: AtomicBool = AtomicBool::new(false);
 
-impl std::ops::Deref for TextureSwapChain {
-    type Target = vr::ovrTextureSwapChain;
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
+/// Set by the eject thread for the render thread to turn VR off, and cleared
+/// by whichever of them gets to it first.
+static VREjectRequested: AtomicBool = AtomicBool::new(false);
+/// Set by the render thread once VR is off for the eject.
+static VREjectReady: AtomicBool = AtomicBool::new(false);
+
+/// How long the eject thread waits for AW to render, which turns VR off.
+const EJECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The `aw_vr_payload::feature` bits the injector set.
+static VRFeatures: AtomicU32 = AtomicU32::new(0);
+
+lazy_static! {
+    static ref VRConfig: Mutex<Config> = Mutex::new(Config::default());
+    static ref VRConfigWatcher: Mutex<Option<ConfigWatcher>> = Mutex::new(None);
+    static ref VRHooks: Mutex<HookRegistry> = Mutex::new(HookRegistry::new());
+    static ref VRBackend: Mutex<Option<Box<dyn VrBackend>>> = Mutex::new(None);
+    static ref VRStereo: Mutex<Stereo> = Mutex::new(Stereo::new(Config::default().pixel_density));
+    static ref VREyeBuffer: Mutex<EyeBuffer> = Mutex::new(EyeBuffer::new());
+    static ref VRWindowSize: Mutex<Option<(u32, u32)>> = Mutex::new(None);
+    /// The world drawn during the current world camera pass, if any.
+    static ref VRWorld: Mutex<Option<usize>> = Mutex::new(None);
+    static ref VRViewWindow: Mutex<ViewWindow> = Mutex::new(ViewWindow::from_fov(Fov::square()));
+    /// The view window AW last asked for the world camera, and which camera
+    /// that was, to put back when VR is turned off.
+    static ref VRAwViewWindow: Mutex<Option<(usize, [f32; 2])>> = Mutex::new(None);
+    static ref VRKeyboard: Mutex<Keyboard> = Mutex::new(Keyboard::new());
+    static ref VRInput: Mutex<InputMapper> = Mutex::new(InputMapper::new(Config::default().bindings()));
+    static ref VRLocomotion: Mutex<Locomotion> = Mutex::new(Locomotion::new());
+    /// When input was last applied.
+    static ref VRInputTime: Mutex<Option<Instant>> = Mutex::new(None);
+    /// Created on first use.
+    static ref VRLeftMatrix: Mutex<Matrix> = Mutex::new(Matrix(ptr::null_mut()));
+    static ref VRStatsReported: Mutex<Instant> = Mutex::new(Instant::now());
 }
 
-impl std::ops::DerefMut for TextureSwapChain {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+/// # Safety
+///
+/// Only EasyHook calls this, with the `REMOTE_ENTRY_INFO` for the injection.
+#[export_name="_NativeInjectionEntryPoint_4"] // EasyHook32.dll has been hex edited to look for this
+#[allow(non_snake_case)]
+pub unsafe extern "system" fn NativeInjectionEntryPoint(remote_info: *mut easyhook::REMOTE_ENTRY_INFO) {
+    let user_data = easyhook::user_data(remote_info);
+    let payload = if user_data.is_empty() {
+        Ok(None)
+    } else {
+        InjectionPayload::decode(user_data).map(Some)
+    };
+    let mut settings = logging::LogSettings::from_env();
+    if let Ok(Some(ref payload)) = payload {
+        if let Some(ref dir) = payload.log_dir {
+            settings.dir = dir.clone();
+        }
+        settings.debugger |= payload.has_feature(feature::LOG_DEBUGGER);
+    }
+    let log_result = logging::init(&settings);
+    info!("AW_VR {} injected", env!("CARGO_PKG_VERSION"));
+    if let Err(error) = log_result {
+        warn!("unable to open the log in {}: {}", settings.dir.display(), error);
+    }
+    let payload = match payload {
+        Ok(payload) => payload,
+        Err(error) => {
+            error!("ignoring what the injector sent: {}", error);
+            None
+        },
+    };
+    if let Some(ref payload) = payload {
+        info!("injected by aw_vr_injector {} with features 0x{:X}", payload.injector_version, payload.features);
+        VRFeatures.store(payload.features, Ordering::SeqCst);
+    }
+    match payload.as_ref().map(|payload| &payload.config) {
+        Some(ConfigSource::Path(path)) => load_config(Some(path.clone())),
+        Some(ConfigSource::Inline(text)) => match Config::parse(text) {
+            Ok(config) => {
+                info!("using the settings the injector sent: {:?}", config);
+                apply_config(config);
+            },
+            Err(error) => error!("ignoring the settings the injector sent, using the defaults: {}", error),
+        },
+        Some(ConfigSource::Default) | None => load_config(None),
+    }
+    // The eye buffers are only sized once, so build the stereo state from the
+    // settings as loaded.
+    *lock(&VRStereo) = Stereo::new(lock(&VRConfig).pixel_density);
+    let result = match panic::catch_unwind(install_hooks) {
+        Ok(result) => result,
+        Err(payload) => Err(AwVrError::from_panic(payload)),
+    };
+    if let Err(error) = result {
+        VRDisabled.store(true, Ordering::SeqCst);
+        report_error("installing the hooks", &error);
+        // With VR off the hooks only pass calls through, so take them out again.
+        if let Err(error) = lock(&VRHooks).remove_all() {
+            error!("unable to remove the hooks that did go in: {}", error);
+        }
+    }
+    if let Err(error) = eject::listen(eject) {
+        warn!("AW_VR can't be ejected: {}", error);
+    }
+    // If the injector launched AW, it's suspended until now, hooks or not.
+    if payload.is_some_and(|payload| payload.has_feature(feature::LAUNCHED)) {
+        match easyhook::rh_wake_up_process() {
+            Ok(()) => info!("woke AW up"),
+            Err(error) => error!("unable to wake AW up: {}", error),
+        }
     }
 }
 
-impl Drop for TextureSwapChain {
-    fn drop(&mut self) {
-        unsafe {
-            vr::ovr_DestroyTextureSwapChain(**VRSession, self.0);
+static_hooks! {
+    static RwCameraBeginUpdate: extern "C" fn(*mut c_void) -> *mut c_void =
+        ("rw_opengl", "rw_camera_begin_update") => rw_camera_begin_update_hook;
+    static RwCameraEndUpdate: extern "C" fn(*mut c_void) -> *mut c_void =
+        ("rw_opengl", "rw_camera_end_update") => rw_camera_end_update_hook;
+    static RwCameraSetViewWindow: extern "C" fn(*mut c_void, *mut f32) -> *mut c_void =
+        ("rw_opengl", "rw_camera_set_view_window") => rw_camera_set_view_window_hook;
+    static RwCameraResize: extern "C" fn(*mut c_void, i32, i32) -> *mut c_void =
+        ("rw_opengl", "rw_camera_resize") => rw_camera_resize_hook;
+    /// Not every build of rw_opengl exports this; without it eyes alternate between frames.
+    static RwWorldRender: extern "C" fn(*mut c_void) -> *mut c_void =
+        ("rw_opengl", "rw_world_render") => rw_world_render_hook;
+}
+
+fn install_hooks() -> Result<(), AwVrError> {
+    // Everything else AW_VR calls has to be there before anything is hooked.
+    rw::functions()?;
+    info!("installing hooks");
+    unsafe {
+        install_hook(&RwCameraBeginUpdate)?;
+        install_hook(&RwCameraEndUpdate)?;
+        install_hook(&RwCameraSetViewWindow)?;
+        install_hook(&RwCameraResize)?;
+        if let Err(error) = install_hook(&RwWorldRender) {
+            warn!("{}; the eyes will take turns", error);
         }
     }
+    Ok(())
 }
 
-#[derive(Debug)]
-struct Matrix(*mut c_void);
-
-unsafe impl Send for Matrix {}
+unsafe fn install_hook<F: Copy>(hook: &'static StaticHook<F>) -> Result<(), AwVrError> {
+    let function = hook.symbol();
+    let installed = hook.install().map_err(|error| AwVrError::Hook { function, error })?;
+    lock(&VRHooks).add(function, installed);
+    info!("hooked {}", function);
+    Ok(())
+}
 
-impl std::ops::Deref for Matrix {
-    type Target = *mut c_void;
-    fn deref(&self) -> &Self::Target {
-        &self.0
+/// Takes AW_VR out of AW, on the eject thread. Returns whether it's safe to
+/// unload the DLL.
+fn eject() -> bool {
+    info!("ejecting");
+    // Left over from an earlier eject that didn't go through.
+    VREjectReady.store(false, Ordering::SeqCst);
+    if !lock(&VRHooks).is_empty() {
+        // The swap chains and eye buffer have to go on the render thread,
+        // where the GL context is current.
+        VREjectRequested.store(true, Ordering::SeqCst);
+        let asked = Instant::now();
+        while !VREjectReady.load(Ordering::SeqCst) {
+            let waited = asked.elapsed();
+            if waited > EJECT_TIMEOUT && VREjectRequested.swap(false, Ordering::SeqCst) {
+                error!("AW didn't render for {} seconds, so AW_VR stays in", EJECT_TIMEOUT.as_secs());
+                return false;
+            }
+            // Otherwise the render thread took the request, late or not, and
+            // only has to finish with it.
+            if waited > EJECT_TIMEOUT * 2 && !VREjectReady.load(Ordering::SeqCst) {
+                error!("AW didn't finish turning VR off, so AW_VR stays in");
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        if let Err(error) = lock(&VRHooks).remove_all() {
+            error!("AW_VR stays in, as not every hook came out: {}", error);
+            return false;
+        }
     }
+    lock(&VRKeyboard).release_all();
+    info!("ejected");
+    true
 }
 
-impl std::ops::DerefMut for Matrix {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+/// Turns VR off for good and frees its GL objects, if the eject thread asked.
+/// Only call this from the render thread, outside `guard`.
+fn turn_off_for_eject() {
+    if !VREjectRequested.swap(false, Ordering::SeqCst) {
+        return;
     }
+    if !VRDisabled.swap(true, Ordering::SeqCst) {
+        if let Err(payload) = panic::catch_unwind(disable_vr) {
+            report_error("turning VR off", &AwVrError::from_panic(payload));
+        }
+    }
+    *lock(&VREyeBuffer) = EyeBuffer::new();
+    VREjectReady.store(true, Ordering::SeqCst);
 }
 
-lazy_static! {
-    static ref GL: lib::Library = lib::Library::new("OPENGL32").unwrap();
-    static ref RW: lib::Library = lib::Library::new("rw_opengl").unwrap();
-    static ref glViewport: lib::Symbol<'static, extern "system" fn(i32, i32, u32, u32)> = unsafe { GL.get(b"glViewport\0") }.unwrap();
-    static ref glGetIntegerv: lib::Symbol<'static, extern "system" fn(i32, *mut i32)> = unsafe { GL.get(b"glGetIntegerv\0") }.unwrap();
-    static ref glBindTexture: lib::Symbol<'static, extern "system" fn(i32, u32)> = unsafe { GL.get(b"glBindTexture\0") }.unwrap();
-    static ref glReadBuffer: lib::Symbol<'static, extern "system" fn(i32)> = unsafe { GL.get(b"glReadBuffer\0") }.unwrap();
-    static ref glCopyPixels: lib::Symbol<'static, extern "system" fn(i32, i32, u32, u32, i32)> = unsafe { GL.get(b"glCopyPixels\0") }.unwrap();
-    static ref glCopyTexImage2D: lib::Symbol<'static, extern "system" fn(i32, i32, i32, i32, i32, u32, u32, i32)> = unsafe { GL.get(b"glCopyTexImage2D\0") }.unwrap();
-    static ref glCopyTexSubImage2D: lib::Symbol<'static, extern "system" fn(i32, i32, i32, i32, i32, i32, u32, u32)> = unsafe { GL.get(b"glCopyTexSubImage2D\0") }.unwrap();
-    static ref glEnable: lib::Symbol<'static, extern "system" fn(i32)> = unsafe { GL.get(b"glEnable\0") }.unwrap();
-    static ref glDisable: lib::Symbol<'static, extern "system" fn(i32)> = unsafe { GL.get(b"glDisable\0") }.unwrap();
-    static ref glGetError: lib::Symbol<'static, extern "system" fn() -> i32> = unsafe { GL.get(b"glGetError\0") }.unwrap();
-    static ref rw_camera_begin_update: lib::Symbol<'static, extern "C" fn(*mut c_void) -> *mut c_void> = unsafe { RW.get(b"rw_camera_begin_update\0") }.unwrap();
-    static ref rw_camera_end_update: lib::Symbol<'static, extern "C" fn(*mut c_void) -> *mut c_void> = unsafe { RW.get(b"rw_camera_end_update\0") }.unwrap();
-    static ref rw_frame_translate: lib::Symbol<'static, extern "C" fn(*mut c_void, *mut f32, u32) -> *mut c_void> = unsafe { RW.get(b"rw_frame_translate\0") }.unwrap();
-    static ref rw_frame_rotate: lib::Symbol<'static, extern "C" fn(*mut c_void, *mut f32, f32, u32) -> *mut c_void> = unsafe { RW.get(b"rw_frame_rotate\0") }.unwrap();
-    static ref rw_camera_set_view_window: lib::Symbol<'static, extern "C" fn(*mut c_void, *mut f32) -> *mut c_void> = unsafe { RW.get(b"rw_camera_set_view_window\0") }.unwrap();
-    static ref rw_camera_resize: lib::Symbol<'static, extern "C" fn(*mut c_void, i32, i32) -> *mut c_void> = unsafe { RW.get(b"rw_camera_resize\0") }.unwrap();
-    static ref rw_frame_get_matrix: lib::Symbol<'static, extern "C" fn(*mut c_void) -> *mut c_void> = unsafe { RW.get(b"rw_frame_get_matrix\0") }.unwrap();
-    static ref rw_matrix_create: lib::Symbol<'static, extern "C" fn() -> *mut c_void> = unsafe { RW.get(b"rw_matrix_create\0") }.unwrap();
-    static ref rw_matrix_copy: lib::Symbol<'static, extern "C" fn(*mut c_void, *mut c_void) -> *mut c_void> = unsafe { RW.get(b"rw_matrix_copy\0") }.unwrap();
+/// The RenderWare function `hook` replaced, to call without going through the
+/// hook again.
+fn original<F: Copy>(hook: &StaticHook<F>) -> Result<F, AwVrError> {
+    hook.original().ok_or(AwVrError::Symbol { library: "rw_opengl", symbol: hook.symbol() })
 }
 
-lazy_static! {
-    static ref VRSession: Session = {
-        unsafe {
-            let init = vr::ovrInitParams {
-                Flags: 0,
-                RequestedMinorVersion: vr::OVR_MINOR_VERSION,
-                LogCallback: None,
-                UserData: 0,
-                ConnectionTimeoutMS: 0,
-                .. mem::uninitialized()
-            };
-            let result = vr::ovr_Initialize(&init as *const _);
-            let mut session: vr::ovrSession = mem::uninitialized();
-            let mut luid: vr::ovrGraphicsLuid = mem::uninitialized();
-            let result = vr::ovr_Create(&mut session as *mut _, &mut luid as *mut _);
-            Session(session)
-        }
+fn load_config(passed: Option<PathBuf>) {
+    let path = match config::locate(passed) {
+        Some(path) => path,
+        None => {
+            info!("no {}, using the default settings", config::FILE_NAME);
+            return;
+        },
     };
-    static ref VRTextureSwapChains: Mutex<Option<[TextureSwapChain; 2]>> = Mutex::new(None);
-    static ref ViewportSize: Mutex<Option<(u32, u32)>> = Mutex::new(None);
-    static ref VRPoses: Mutex<[vr::ovrPosef; 2]> = Mutex::new([zero_posef(), zero_posef()]);
-    static ref VRKeyboard: Mutex<Keyboard> = Mutex::new(Keyboard::new());
-    static ref VRLeftMatrix: Mutex<Matrix> = Mutex::new(unsafe { Matrix(rw_matrix_create()) });
+    *lock(&VRConfigWatcher) = Some(ConfigWatcher::new(path.clone(), Duration::from_secs(1)));
+    match Config::load(&path) {
+        Ok(config) => {
+            info!("loaded settings from {}: {:?}", path.display(), config);
+            apply_config(config);
+        },
+        Err(error) => error!("ignoring {}, using the default settings: {}", path.display(), error),
+    }
 }
 
-#[derive(Debug)]
-struct Keyboard {
-    enigo: Enigo,
-    ctrl: bool,
-    left: bool,
-    right: bool,
-    up: bool,
-    down: bool,
-    plus: bool,
-    minus: bool
-}
-
-impl Keyboard {
-    fn new() -> Self {
-        Keyboard {
-            enigo: Enigo::new(),
-            ctrl: false,
-            left: false,
-            right: false,
-            up: false,
-            down: false,
-            plus: false,
-            minus: false
-        }
+/// Picks up changes to the config file. Only call this between frames, so
+/// both eyes of a frame are drawn with the same settings.
+fn reload_config() {
+    let mut watcher = lock(&VRConfigWatcher);
+    let watcher = match *watcher {
+        Some(ref mut watcher) => watcher,
+        None => return,
+    };
+    match watcher.poll() {
+        Some(Ok(config)) => {
+            info!("reloaded settings from {}: {:?}", watcher.path().display(), config);
+            apply_config(config);
+        },
+        Some(Err(error)) => error!("ignoring changes to {}, keeping the previous settings: {}", watcher.path().display(), error),
+        None => {},
     }
-    
-    fn status(&mut self, key: Key) -> &mut bool {
-        match key {
-            Key::Control => &mut self.ctrl,
-            Key::LeftArrow => &mut self.left,
-            Key::RightArrow => &mut self.right,
-            Key::UpArrow => &mut self.up,
-            Key::DownArrow => &mut self.down,
-            Key::Layout('+') => &mut self.plus,
-            Key::Layout('-') => &mut self.minus,
-            _ => panic!("Unknown key!")
-        }
+}
+
+fn apply_config(mut config: Config) {
+    let mut current = lock(&VRConfig);
+    for setting in config.ignored_settings() {
+        warn!("{} only applies with locomotion.mode = \"analog\", so it's ignored", setting);
     }
-    
-    fn hold(&mut self, key: Key) {
-        let status = *self.status(key);
-        if !status {
-            self.enigo.key_down(key);
-            *self.status(key) = true;
-        }
+    if lock(&VRBackend).is_some() && config.tracking_origin != current.tracking_origin {
+        warn!("tracking_origin can't change while the headset is in use; it takes effect once AW_VR is injected again");
+        config.tracking_origin = current.tracking_origin;
     }
-    
-    fn release(&mut self, key: Key) {
-        let status = *self.status(key);
-        if status {
-            self.enigo.key_up(key);
-            *self.status(key) = false;
-        }
+    if lock(&VRBackend).is_some() && config.pixel_density != current.pixel_density {
+        warn!("pixel_density can't change while the headset is in use; it takes effect once AW_VR is injected again");
+        config.pixel_density = current.pixel_density;
     }
-    
+    let bindings = config.bindings();
+    if bindings != current.bindings() || config.locomotion != current.locomotion || config.keys != current.keys {
+        // Otherwise a key held for the old bindings would never be let go.
+        *lock(&VRInput) = InputMapper::new(bindings);
+        lock(&VRKeyboard).release_all();
+    }
+    log::set_max_level(config.log_level.unwrap_or_else(|| logging::LogSettings::from_env().level));
+    *current = config;
 }
 
-fn zero_posef() -> vr::ovrPosef {
-    unsafe {
-        vr::ovrPosef {
-            Position: vr::ovrVector3f {
-                x: 0.0,
-                y: 0.0,
-                z: 0.0,
-                .. mem::uninitialized()
-            },
-            Orientation: vr::ovrQuatf {
-                x: 0.0,
-                y: 0.0,
-                z: 0.0,
-                w: 1.0,
-                .. mem::uninitialized()
-            },
-            .. mem::uninitialized()
-        }
-    }
+/// Locks `mutex` even if a panic poisoned it. Whatever the hooks left behind
+/// still has to be cleaned up.
+fn lock<'a, T>(mutex: &'a Mutex<T>) -> MutexGuard<'a, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
 }
 
-fn texture_swap_chain(width: i32, height: i32) -> TextureSwapChain {
-    let desc = vr::ovrTextureSwapChainDesc {
-        Type: vr::ovrTexture_2D,
-        Format: vr::OVR_FORMAT_R8G8B8A8_UNORM_SRGB,
-        ArraySize: 1,
-        Width: width,
-        Height: height,
-        MipLevels: 1,
-        SampleCount: 1,
-        StaticImage: 0,
-        MiscFlags: 0,
-        BindFlags: 0
+/// Runs the VR side of a hook. If it fails or panics, VR is turned off for good
+/// and AW's rendering put back the way AW set it up.
+fn guard<F: FnOnce() -> Result<(), AwVrError>>(during: &'static str, f: F) {
+    if VRDisabled.load(Ordering::SeqCst) {
+        return;
+    }
+    let error = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return,
+        Ok(Err(error)) => error,
+        Err(payload) => AwVrError::from_panic(payload),
     };
-    unsafe {
-        let mut tsc = mem::uninitialized();
-        vr::opengl::ovr_CreateTextureSwapChainGL(**VRSession, &desc, &mut tsc);
-        TextureSwapChain(tsc)
+    VRDisabled.store(true, Ordering::SeqCst);
+    report_error(during, &error);
+    if let Err(payload) = panic::catch_unwind(disable_vr) {
+        report_error("turning VR off", &AwVrError::from_panic(payload));
     }
 }
 
-#[export_name="_NativeInjectionEntryPoint_4"] // EasyHook32.dll has been hex edited to look for this
-pub extern "stdcall" fn NativeInjectionEntryPoint(_remote_info: *mut c_void) {
-    unsafe {
-        use std::fs::File;
-        use std::mem;
-        File::create("about_to_install_hook.txt").unwrap();
-
-        lazy_static::initialize(&VRSession);
-        vr::ovr_SetTrackingOriginType(**VRSession, vr::ovrTrackingOrigin_FloorLevel);
-        
-        //lh_install_hook(**glViewport as *mut _, glViewportHook as *mut _);
-        lh_install_hook(**rw_camera_begin_update as *mut _, rw_camera_begin_update_hook as *mut _);
-        lh_install_hook(**rw_camera_end_update as *mut _, rw_camera_end_update_hook as *mut _);
-        lh_install_hook(**rw_camera_set_view_window as *mut _, rw_camera_set_view_window_hook as *mut _);
-        lh_install_hook(**rw_camera_resize as *mut _, rw_camera_resize_hook as *mut _);
-        let error = error_string();
-        File::create("installed_hook.txt").unwrap();
-        let mut errors = File::create("hook_errors.txt").unwrap();
-        writeln!(&mut errors, "Error: {:?}", error);
-        drop(errors);
-        
+/// Undoes what the eye passes do to AW's rendering, and lets go of the headset.
+fn disable_vr() {
+    warn!("turning VR off");
+    lock(&VRKeyboard).release_all();
+    let main_camera = lock(&VRStereo).main_camera().map(|camera| camera as *mut c_void);
+    let mut eye_buffer = lock(&VREyeBuffer);
+    let window = lock(&VRWindowSize).unwrap_or_else(|| eye_buffer.size());
+    if eye_buffer.is_bound() {
+        let _ = eye_buffer.unbind(window);
+        if let (Some(camera), Ok(resize)) = (main_camera, original(&RwCameraResize)) {
+            resize(camera, window.0 as i32, window.1 as i32);
+        }
     }
+    // Only the world camera is put back. Any other camera AW passed may have
+    // been destroyed since.
+    if let (Some(camera), Ok(rw), Ok(set_view_window)) = (main_camera, rw::functions(), original(&RwCameraSetViewWindow)) {
+        if let Some((_, mut view_window)) = lock(&VRAwViewWindow).filter(|&(aw_camera, _)| aw_camera == camera as usize) {
+            set_view_window(camera, view_window.as_mut_ptr());
+        }
+        (rw.rw_camera_set_view_offset)(camera, [0.0, 0.0].as_mut_ptr());
+    }
+    drop(lock(&VRBackend).take());
 }
 
-static counter: AtomicUsize = AtomicUsize::new(0);
+fn report_error(during: &str, error: &AwVrError) {
+    error!("VR turned off after {} failed: {}", during, error);
+}
 
+/// Calls through to the function `hook` replaced. A hook only runs once it's
+/// installed, so the null is never actually returned.
+fn call_original<F: Copy, C: FnOnce(F) -> *mut c_void>(hook: &StaticHook<F>, call: C) -> *mut c_void {
+    hook.original().map(call).unwrap_or(ptr::null_mut())
+}
 
-pub extern "system" fn glViewportHook(x: i32, y: i32, width: u32, height: u32) {
-    *ViewportSize.lock().unwrap() = Some((width, height));
+/// Runs `f` with the VR backend, creating it on first use. Only call this from
+/// AW's render thread.
+fn with_backend<T, F: FnOnce(&mut dyn VrBackend) -> Result<T, AwVrError>>(f: F) -> Result<T, AwVrError> {
+    let mut backend = lock(&VRBackend);
+    let backend = match *backend {
+        Some(ref mut backend) => backend,
+        None => {
+            let created = backend::create(lock(&VRConfig).tracking_origin, VRFeatures.load(Ordering::SeqCst) & feature::OPENXR != 0)?;
+            info!("using the {} backend", created.name());
+            backend.get_or_insert(created)
+        },
+    };
+    f(&mut **backend)
 }
 
-fn camera_get_frame(camera: *mut c_void) -> *mut c_void {
-    unsafe {
-        let camera_as_ptrs = camera as *mut usize;
-        let ptr_to_frame = camera_as_ptrs.offset(1);
-        *ptr_to_frame as *mut c_void
+/// Logs the frame timing of roughly the last second.
+fn report_frame_stats(backend: &mut dyn VrBackend) {
+    let mut reported = lock(&VRStatsReported);
+    if reported.elapsed() < Duration::from_secs(1) {
+        return;
     }
+    *reported = Instant::now();
+    let stats = backend.take_frame_stats();
+    let ms = |seconds: Option<f64>| seconds.map_or("unknown".to_owned(), |seconds| format!("{:.1}ms", seconds * 1000.0));
+    info!("{} frames ({:.1} fps), frame time {} mean {} max, sensors to submit {}, sensors to display {}",
+        stats.frames, stats.frames_per_second(), ms(Some(stats.mean_frame_time)), ms(Some(stats.max_frame_time)),
+        ms(stats.mean_render_latency), ms(stats.mean_display_latency));
 }
 
-pub extern "C" fn rw_camera_begin_update_hook(camera: *mut c_void) -> *mut c_void {
-    let current = counter.load(Ordering::SeqCst);
-    unsafe {
-        let mut status = mem::zeroed();
-        vr::ovr_GetSessionStatus(**VRSession, &mut status);
-        if status.ShouldRecenter != 0 {
-            vr::ovr_RecenterTrackingOrigin(**VRSession);
-        }
-        let mut input_state = mem::zeroed();
-        vr::ovr_GetInputState(**VRSession, vr::ovrControllerType_Touch, &mut input_state);
-        if input_state.Buttons & (vr::ovrButton_Enter as u32) != 0 {
-            vr::ovr_RecenterTrackingOrigin(**VRSession);
-        }
-        if input_state.Thumbstick[0].y != 0.0 || input_state.Thumbstick[1].x != 0.0 {
-            let mut keyboard = VRKeyboard.lock().unwrap();
-            let x = input_state.Thumbstick[1].x;
-            let y = input_state.Thumbstick[0].y;
-            let ctrl = y.abs() >= 0.75;
-            if ctrl {
-                keyboard.hold(Key::Control);
-            } else {
-                keyboard.release(Key::Control);
-            }
-            if y > 0.5 {
-                keyboard.hold(Key::UpArrow);
-            } else {
-                keyboard.release(Key::UpArrow);
-            }
-            if y < -0.5 {
-                keyboard.hold(Key::DownArrow);
-            } else {
-                keyboard.release(Key::DownArrow);
-            }
-            if x > 0.5 {
-                keyboard.hold(Key::RightArrow);
-            } else {
-                keyboard.release(Key::RightArrow);
-            }
-            if x < -0.5 {
-                keyboard.hold(Key::LeftArrow);
-            } else {
-                keyboard.release(Key::LeftArrow);
-            }
+extern "C" fn rw_camera_begin_update_hook(camera: *mut c_void) -> *mut c_void {
+    turn_off_for_eject();
+    guard("rw_camera_begin_update", || {
+        pin_hooks_to_render_thread();
+        begin_eye_pass(camera)
+    });
+    call_original(&RwCameraBeginUpdate, |begin_update| begin_update(camera))
+}
+
+/// Limits the hooks to the thread AW first renders on, so no other thread,
+/// AW's or EasyHook's, ever runs them.
+fn pin_hooks_to_render_thread() {
+    if VRHooksPinned.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    match lock(&VRHooks).set_acl(&Acl::Inclusive(vec![easyhook::CURRENT_THREAD])) {
+        Ok(()) => info!("hooks limited to the render thread"),
+        Err(error) => warn!("unable to limit the hooks to the render thread: {}", error),
+    }
+}
+
+/// Points `camera` at the next eye, if it's the world camera.
+fn begin_eye_pass(camera: *mut c_void) -> Result<(), AwVrError> {
+    let rw = rw::functions()?;
+    let gl = gl::functions()?;
+    let config = lock(&VRConfig).clone();
+    let pass = with_backend(|backend| {
+        let pass = lock(&VRStereo).begin_eye(backend, camera as usize)?;
+        if pass.is_some() {
+            let scale = config.world_scale;
+            backend.set_depth_range(rw.camera_clip_planes(camera).map(|(near, far)| (near * scale, far * scale)));
         }
+        Ok(pass)
+    })?;
+    let pass = match pass {
+        Some(pass) => pass,
+        None => return Ok(()),
+    };
+    *lock(&VRWorld) = None;
+    if pass.recentered {
+        // As when the recenter binding does it.
+        lock(&VRLocomotion).recenter();
     }
-    if current&1 == 0 {
-        unsafe {
-            let fov = vr::ovrFovPort {
-                UpTan: 1.0,
-                DownTan: 1.0,
-                LeftTan: 1.0,
-                RightTan: 1.0,
-                .. mem::uninitialized()
-            };
-            let left_eye_hmd_offset = vr::ovr_GetRenderDesc(**VRSession, vr::ovrEye_Left, fov).HmdToEyeOffset;
-            let right_eye_hmd_offset = vr::ovr_GetRenderDesc(**VRSession, vr::ovrEye_Right, fov).HmdToEyeOffset;
-            let mut poses = [zero_posef(), zero_posef()];
-            vr::ovr_GetEyePoses(**VRSession, 0, 1, &[left_eye_hmd_offset, right_eye_hmd_offset], (&mut poses).as_mut_ptr() as *const _, std::ptr::null_mut());
-            scale_posef(&mut poses[0]);
-            scale_posef(&mut poses[1]);
-            *VRPoses.lock().unwrap() = poses;
+    if pass.eye == Eye::Left {
+        // AW's camera hasn't been moved to an eye yet.
+        let heading = rw::matrix_heading((rw.rw_frame_get_matrix)(camera_get_frame(camera)));
+        apply_input(&pass.input, &pass.pose, heading, &config)?;
+    }
+    if let Some((width, height)) = pass.size {
+        let mut window = lock(&VRWindowSize);
+        if window.is_none() {
+            let mut viewport = [0i32, 0, 0, 0];
+            (gl.glGetIntegerv)(GL_VIEWPORT, viewport.as_mut_ptr());
+            *window = Some((viewport[2] as u32, viewport[3] as u32));
         }
+        lock(&VREyeBuffer).bind((width, height))?;
+        gl.check_error("binding the eye buffer")?;
+        original(&RwCameraResize)?(camera, width as i32, height as i32);
     }
-    let eye = current&1;
+    let eye = pass.eye;
     let frame = camera_get_frame(camera);
-    let frame_matrix = rw_frame_get_matrix(frame);
-    let left_matrix = VRLeftMatrix.lock().unwrap().0;
-    if eye == 0 {
-        rw_matrix_copy(left_matrix, frame_matrix);
+    let frame_matrix = (rw.rw_frame_get_matrix)(frame);
+    let mut left_matrix = lock(&VRLeftMatrix);
+    if left_matrix.is_null() {
+        **left_matrix = (rw.rw_matrix_create)();
+    }
+    if eye == Eye::Left {
+        (rw.rw_matrix_copy)(**left_matrix, frame_matrix);
     } else {
-        rw_matrix_copy(frame_matrix, left_matrix);
+        (rw.rw_matrix_copy)(frame_matrix, **left_matrix);
     }
-    let eye_pose = VRPoses.lock().unwrap()[eye];
-    rw_frame_translate(frame, (&mut [0.0, -0.17 * 0.9, 0.0]).as_mut_ptr(), 1);
-    rw_frame_translate(frame, (&mut [eye_pose.Position.x, eye_pose.Position.y, eye_pose.Position.z]).as_mut_ptr(), 1);
+    // Turned with the avatar as it lines up with the locomotion reference.
+    let mut eye_pose = Pose::from_yaw(lock(&VRLocomotion).yaw(), [0.0; 3]).compose(&pass.pose);
+    scale_pose(&mut eye_pose, &config);
+    if config.tracking_origin == TrackingOrigin::Floor {
+        (rw.rw_frame_translate)(frame, [0.0, -config.to_world(config.eye_height), 0.0].as_mut_ptr(), 1);
+    }
+    (rw.rw_frame_translate)(frame, eye_pose.position.as_mut_ptr(), 1);
     let (axis, angle) = axis_angle(&eye_pose);
-    rw_frame_rotate(frame, (&mut [-axis.0, axis.1, -axis.2]).as_mut_ptr(), angle.to_degrees(), 1);
+    (rw.rw_frame_rotate)(frame, [-axis.0, axis.1, -axis.2].as_mut_ptr(), angle.to_degrees(), 1);
     //rw_frame_rotate(frame, (&mut [0.0, 1.0, 0.0]).as_mut_ptr(), 360.0 + 90.0, 1);
-    let result = rw_camera_begin_update(camera);
-    result
+    let mut view = ViewWindow::from_fov(pass.fov);
+    *lock(&VRViewWindow) = view;
+    (rw.rw_frame_translate)(frame, view.eye_correction().as_mut_ptr(), 1);
+    original(&RwCameraSetViewWindow)?(camera, view.window.as_mut_ptr());
+    (rw.rw_camera_set_view_offset)(camera, view.offset.as_mut_ptr());
+    Ok(())
 }
 
-fn scale_posef(pose: &mut vr::ovrPosef) {
-    pose.Position.x /= -10.0;
-    pose.Position.y /= 10.0;
-    pose.Position.z /= -10.0;
+/// Presses and lets go of whatever the bindings say for this frame's input.
+fn apply_input(input: &InputState, head: &Pose, heading: f32, config: &Config) -> Result<(), AwVrError> {
+    let now = Instant::now();
+    let seconds = lock(&VRInputTime).replace(now).map_or(0.0, |last| duration_seconds(now - last));
+    let mut keys = if config.locomotion.mode == LocomotionMode::Analog {
+        let frame = Frame { input: *input, head: *head, heading, seconds };
+        lock(&VRLocomotion).update(&frame, &config.locomotion, &config.keys)
+    } else {
+        Vec::new()
+    };
+    let output = lock(&VRInput).update(input);
+    for key in output.keys {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    {
+        let mut keyboard = lock(&VRKeyboard);
+        keyboard.hold_only(&keys);
+        keyboard.hold_only_buttons(&output.buttons);
+        for &wheel in &output.wheel {
+            keyboard.scroll(wheel);
+        }
+    }
+    for action in output.actions {
+        match action {
+            VrAction::Recenter => {
+                with_backend(|backend| Ok(backend.recenter()?))?;
+                lock(&VRLocomotion).recenter();
+            },
+        }
+    }
+    Ok(())
 }
 
-fn axis_angle(pose: &vr::ovrPosef) -> ((f32, f32, f32), f32) {
-    let (w, x, y, z) = (pose.Orientation.w, pose.Orientation.x, pose.Orientation.y, pose.Orientation.z);
+fn duration_seconds(duration: Duration) -> f32 {
+    duration.as_secs() as f32 + duration.subsec_nanos() as f32 * 1e-9
+}
+
+fn scale_pose(pose: &mut Pose, config: &Config) {
+    pose.position[0] = -config.to_world(pose.position[0]);
+    pose.position[1] = config.to_world(pose.position[1]);
+    pose.position[2] = -config.to_world(pose.position[2]);
+}
+
+fn axis_angle(pose: &Pose) -> ((f32, f32, f32), f32) {
+    let [x, y, z, w] = pose.orientation;
     let (mut x, mut y, mut z) = {
         let len = (x*x + y*y + z*z).sqrt();
         if len == 0.0 {
@@ -373,114 +581,91 @@ fn axis_angle(pose: &vr::ovrPosef) -> ((f32, f32, f32), f32) {
     ((x, y, z), angle)
 }
 
-fn layer(tsc: &[TextureSwapChain], viewport_size: (u32, u32), poses: &[vr::ovrPosef]) -> vr::ovrLayerEyeFov {
-    let (width, height) = viewport_size;
-    unsafe { 
-        let viewport = vr::ovrRecti {
-            Pos: vr::ovrVector2i {
-                x: 0,
-                y: 0,
-                .. mem::uninitialized()
-            },
-            Size: vr::ovrSizei {
-                w: width as i32,
-                h: height as i32,
-                .. mem::uninitialized()
-            },
-            .. mem::uninitialized()
-        };
-        let fov = vr::ovrFovPort {
-            UpTan: 1.0,
-            DownTan: 1.0,
-            LeftTan: 1.0,
-            RightTan: 1.0,
-            .. mem::uninitialized()
-        };
-        vr::ovrLayerEyeFov {
-            Header: vr::ovrLayerHeader {
-                Type: vr::ovrLayerType_EyeFov,
-                Flags: vr::ovrLayerFlag_TextureOriginAtBottomLeft as u32,
-                .. mem::uninitialized()
-            },
-            ColorTexture: [*tsc[0], *tsc[1]],
-            Viewport: [viewport, viewport],
-            Fov: [fov, fov],
-            RenderPose: [poses[0], poses[1]],
-            SensorSampleTime: 0.0,
-            .. mem::uninitialized()
-        }
-    }
-}
-
-fn check_error<S: AsRef<str>>(where_: S) {
-    let error = glGetError();
-    if error != 0 {
-        use std::fs::File;
-        
-        let mut file = File::create("vrerror.txt").unwrap();
-        write!(&mut file, "GL Error! Error code: 0x{:X} after doing: {}", error, where_.as_ref()).unwrap();
-        panic!("GL ERROR");
-    }
+extern "C" fn rw_camera_end_update_hook(camera: *mut c_void) -> *mut c_void {
+    let result = call_original(&RwCameraEndUpdate, |end_update| end_update(camera));
+    guard("rw_camera_end_update", || end_eye_pass(camera));
+    result
 }
 
-pub extern "C" fn rw_camera_end_update_hook(camera: *mut c_void) -> *mut c_void {
-    let result = rw_camera_end_update(camera);
-    let current = counter.load(Ordering::SeqCst);
-    let eye: usize = current&1;
-    unsafe {
-        let mut texid = 0;
-        let mut tsc_lock = VRTextureSwapChains.lock().unwrap();
-        if tsc_lock.is_none() {
-            let mut viewport = [0i32, 0, 0, 0];
-            glGetIntegerv(0x0BA2, viewport.as_mut_ptr());
-            let (width, height) = (viewport[2] as u32, viewport[3] as u32);
-            //let (width, height) = (512, 512);
-            *tsc_lock = Some([texture_swap_chain(width as i32, height as i32), texture_swap_chain(width as i32, height as i32)]);
-            *ViewportSize.lock().unwrap() = Some((width, height));
-        }
-        let tsc = tsc_lock.as_ref().unwrap();
-        vr::opengl::ovr_GetTextureSwapChainBufferGL(**VRSession, *tsc[eye], -1, &mut texid);
-        if texid == 0 {
-            panic!("0 texid");
+/// Hands what `camera` drew to the headset, if it's the world camera.
+fn end_eye_pass(camera: *mut c_void) -> Result<(), AwVrError> {
+    let gl = gl::functions()?;
+    let mut eye_buffer = lock(&VREyeBuffer);
+    let eye = with_backend(|backend| {
+        let eye = lock(&VRStereo).end_eye(backend, camera as usize, |textures| {
+            if textures.color == 0 {
+                return Err(AwVrError::NoTexture);
+            }
+            eye_buffer.copy_to(textures)?;
+            gl.check_error("copying the eye buffer")
+        })?;
+        if eye == Some(Eye::Right) {
+            report_frame_stats(backend);
         }
-        let (width, height) = ViewportSize.lock().unwrap().unwrap();
-        
-        glEnable(0x0DE1);
-        check_error("Enabling GL_TEXTURE_2D");
-        glReadBuffer(0x0404);
-        check_error("glReadBuffer");
-        glBindTexture(0x0DE1, texid);
-        check_error("glBindTexture");
-        glCopyTexSubImage2D(0x0DE1, 0, 0, 0, 0, 0, width, height);
-        glDisable(0x0DE1);
-        check_error("glCopyTexSubImage2D");
-        vr::ovr_CommitTextureSwapChain(**VRSession, *tsc[eye]);
-        if eye == 1 {
-            let poses = VRPoses.lock().unwrap();
-            let layer = layer(&*tsc, (width, height), &*poses);
-            let layers = [&layer as *const _ as *const vr::ovrLayerHeader];
-            vr::ovr_SubmitFrame(**VRSession, 0, std::ptr::null(), (&layers).as_ptr(), 1);
+        Ok(eye)
+    })?;
+    if eye.is_some() && eye_buffer.is_bound() {
+        let (width, height) = lock(&VRWindowSize).unwrap_or_else(|| eye_buffer.size());
+        eye_buffer.unbind((width, height))?;
+        gl.check_error("mirroring the eye buffer")?;
+        original(&RwCameraResize)?(camera, width as i32, height as i32);
+    }
+    drop(eye_buffer);
+    if eye == Some(Eye::Right) {
+        reload_config();
+    }
+    if eye == Some(Eye::Left) {
+        let world = lock(&VRWorld).take();
+        // The world is only remembered when rw_world_render is hooked.
+        if let Some(world) = world {
+            // Draw the right eye straight away, from the same world state, so
+            // both eyes show the same moment. Only the world is redrawn, not
+            // whatever AW drew on top of it.
+            begin_eye_pass(camera)?;
+            original(&RwCameraBeginUpdate)?(camera);
+            original(&RwWorldRender)?(world as *mut c_void);
+            original(&RwCameraEndUpdate)?(camera);
+            end_eye_pass(camera)?;
         }
     }
-    counter.store(current.wrapping_add(1), Ordering::SeqCst);
-    result
+    Ok(())
 }
 
-pub extern "C" fn rw_camera_set_view_window_hook(camera: *mut c_void, view_window: *mut f32) -> *mut c_void {
-    if !view_window.is_null() {
+extern "C" fn rw_world_render_hook(world: *mut c_void) -> *mut c_void {
+    guard("rw_world_render", || {
+        *lock(&VRWorld) = Some(world as usize);
+        Ok(())
+    });
+    call_original(&RwWorldRender, |world_render| world_render(world))
+}
+
+extern "C" fn rw_camera_set_view_window_hook(camera: *mut c_void, view_window: *mut f32) -> *mut c_void {
+    // AW sizes the window to its own aspect ratio; the headset decides instead,
+    // for the world camera only. The minimap, portraits and the like keep
+    // theirs.
+    guard("rw_camera_set_view_window", || {
+        if view_window.is_null() || lock(&VRStereo).main_camera().is_some_and(|main| main != camera as usize) {
+            return Ok(());
+        }
+        let window = lock(&VRViewWindow).window;
         unsafe {
-            //*view_window /= 2.0;
-            *view_window = 1.0;
-            *view_window.offset(1) = 1.0;
+            let requested = [*view_window, *view_window.offset(1)];
+            *lock(&VRAwViewWindow) = Some((camera as usize, requested));
+            *view_window = window[0];
+            *view_window.offset(1) = window[1];
         }
-    }
-    unsafe {
-        rw_camera_set_view_window(camera, view_window)
-    }
+        Ok(())
+    });
+    call_original(&RwCameraSetViewWindow, |set_view_window| set_view_window(camera, view_window))
 }
 
-pub extern "C" fn rw_camera_resize_hook(camera: *mut c_void, width: i32, height: i32) -> *mut c_void {
-    *VRTextureSwapChains.lock().unwrap() = Some([texture_swap_chain(width, height), texture_swap_chain(width, height)]);
-    *ViewportSize.lock().unwrap() = Some((width as u32, height as u32));
-    rw_camera_resize(camera, width, height)
-}
\ No newline at end of file
+/// Only AW's own resizes come through here; the eye passes resize the camera
+/// through the original. AW resizes its world camera along with its window.
+extern "C" fn rw_camera_resize_hook(camera: *mut c_void, width: i32, height: i32) -> *mut c_void {
+    guard("rw_camera_resize", || {
+        lock(&VRStereo).set_main_camera(camera as usize);
+        *lock(&VRWindowSize) = Some((width as u32, height as u32));
+        Ok(())
+    });
+    call_original(&RwCameraResize, |resize| resize(camera, width, height))
+}
@@ -0,0 +1,439 @@
+//! Moving the avatar as far as the thumbsticks are pushed.
+//!
+//! AW only walks, runs, flies or turns at full speed, for as long as a key is held.
+//! Slower speeds come from holding the keys for part of the time: each axis
+//! keeps count of how far it's behind where the stick wants it, and every
+//! frame holds whichever key, if any, brings it closest to caught up by the
+//! next frame.
+//!
+//! Turning with the right stick turns the view by exactly as much as asked,
+//! and the avatar follows. Moving towards the headset or a controller rather
+//! than the avatar's front turns the avatar to face that way. Either way, the
+//! view is turned back by however far AW's camera turned, so the only turns
+//! seen are the ones asked for.
+//!
+//! Only the view's turns are exact. AW has no way to set the avatar's heading,
+//! so the avatar is turned by holding the turn keys, frame by frame, until
+//! AW's camera heading catches up. It lags behind the view while it does, and
+//! can end up off by as much as a frame's worth of turning.
+
+use std::f32::consts::PI;
+
+use backend::{InputState, Pose};
+use config::{KeyBindings, LocomotionReference, LocomotionSettings, Turning};
+use keyboard::Key;
+
+/// How far, in seconds at the speed wanted, an axis can fall behind. Whatever
+/// is owed beyond that is forgotten rather than caught up on later.
+const MAX_LAG: f32 = 0.1;
+
+/// How far, from 0 to 1 past the deadzone, the right stick has to be pushed
+/// to snap.
+const SNAP_AT: f32 = 0.5;
+
+/// How many updates after the avatar was last turned to line it up the view
+/// is still turned back with it, as AW catches up with the keys.
+const SETTLE_UPDATES: u32 = 2;
+
+/// One direction of movement.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Axis {
+    /// How far behind the axis is, in seconds of walking or turning.
+    owed: f32,
+    /// The speed held since the last update, signed like `owed`.
+    held: f32,
+}
+
+impl Axis {
+    /// Accounts for the last `seconds`, in which `speed` was wanted, and picks
+    /// what to hold until the next update from `speeds`, which are in the
+    /// same units and direction as `speed`. Expects the next update to be as
+    /// far away as this one was from the last.
+    fn update(&mut self, speed: f32, seconds: f32, speeds: &[f32]) -> f32 {
+        self.owed += (speed - self.held) * seconds;
+        if speed == 0.0 || self.owed * speed < 0.0 {
+            self.owed = 0.0;
+        }
+        let lag = MAX_LAG * speed.abs().max(1.0);
+        self.owed = self.owed.clamp(-lag, lag);
+        self.hold(seconds, speeds)
+    }
+
+    /// Like `update`, to cover a distance rather than keep up with a speed.
+    fn cover(&mut self, owed: f32, seconds: f32, speeds: &[f32]) -> f32 {
+        self.owed = owed;
+        self.hold(seconds, speeds)
+    }
+
+    fn hold(&mut self, seconds: f32, speeds: &[f32]) -> f32 {
+        let sign = self.owed.signum();
+        let remaining = |held: f32| (self.owed - held * seconds).abs();
+        self.held = if self.owed == 0.0 {
+            0.0
+        } else {
+            speeds.iter()
+                .map(|&held| held * sign)
+                .fold(0.0, |best, held| if remaining(held) < remaining(best) { held } else { best })
+        };
+        self.held
+    }
+}
+
+/// `angle` in radians, brought within half a turn of 0.
+fn wrap(angle: f32) -> f32 {
+    let angle = (angle + PI).rem_euclid(2.0 * PI) - PI;
+    if angle == -PI { PI } else { angle }
+}
+
+/// What a frame's locomotion goes by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub input: InputState,
+    /// The headset's pose, from the tracking origin.
+    pub head: Pose,
+    /// Which way AW's camera faces, as `rw::matrix_heading` gives it.
+    pub heading: f32,
+    /// Since the last frame.
+    pub seconds: f32,
+}
+
+/// Turns the thumbsticks into held keys, frame by frame.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Locomotion {
+    forward: Axis,
+    strafe: Axis,
+    /// Flying up, in seconds of walking.
+    fly: Axis,
+    /// The avatar's turns, positive to the right, in seconds of turning.
+    turn: Axis,
+    /// How far the tracking space is turned from the avatar, in radians
+    /// counter-clockwise seen from above.
+    yaw: f32,
+    /// AW's camera heading at the last update.
+    heading: Option<f32>,
+    /// Updates left in which the avatar's turns are turned back out of the
+    /// view.
+    settling: u32,
+    /// Whether the right stick has snapped since it was last let go.
+    snapped: bool,
+}
+
+impl Locomotion {
+    pub fn new() -> Locomotion {
+        Locomotion::default()
+    }
+
+    /// How far to turn tracked poses, in radians counter-clockwise seen from
+    /// above, before putting them on the avatar.
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    /// Lines the view up with the avatar again, for when the headset is
+    /// recentered.
+    pub fn recenter(&mut self) {
+        self.yaw = 0.0;
+    }
+
+    /// Reads a frame and returns the keys to hold until the next.
+    pub fn update(&mut self, frame: &Frame, settings: &LocomotionSettings, keys: &KeyBindings) -> Vec<Key> {
+        let turned = self.heading.map_or(0.0, |heading| wrap(frame.heading - heading));
+        self.heading = Some(frame.heading);
+        if self.settling > 0 {
+            self.settling -= 1;
+            self.yaw = wrap(self.yaw - turned);
+        }
+
+        let deflection = |value: f32| {
+            let pushed = (value.abs() - settings.deadzone) / (1.0 - settings.deadzone);
+            pushed.clamp(0.0, 1.0) * value.signum()
+        };
+        let [left, right] = frame.input.thumbstick;
+        let seconds = frame.seconds;
+        let walking = [1.0, settings.run_speed];
+        let forward_speed = deflection(left[1]) * settings.run_speed;
+        let strafe_speed = if settings.strafe { deflection(left[0]) * settings.run_speed } else { 0.0 };
+        // Flying takes the right stick only while it's pushed more up or
+        // down than sideways, so turning doesn't drift up or down.
+        let flying = settings.fly && right[1].abs() > right[0].abs();
+        let fly_speed = if flying { deflection(right[1]) * settings.run_speed } else { 0.0 };
+
+        // The view turns as the right stick says, and the avatar follows it,
+        // or the reference while moving.
+        let push = if flying { 0.0 } else { deflection(right[0]) };
+        match settings.turning {
+            Turning::Smooth => self.yaw = wrap(self.yaw - push * settings.turn_speed.to_radians() * seconds),
+            Turning::Snap if push.abs() >= SNAP_AT && !self.snapped => {
+                self.yaw = wrap(self.yaw - push.signum() * settings.snap_angle.to_radians());
+                self.snapped = true;
+            },
+            Turning::Snap => self.snapped &= push != 0.0,
+        }
+        let moving = forward_speed != 0.0 || strafe_speed != 0.0;
+        let reference = match settings.reference {
+            LocomotionReference::Avatar => Some(0.0),
+            LocomotionReference::Head if moving => Some(frame.head.yaw()),
+            LocomotionReference::Controller if moving => frame.input.hands[0].map(|hand| hand.yaw()),
+            _ => None,
+        };
+        let turn = match reference {
+            Some(reference) => {
+                let off = wrap(self.yaw + reference);
+                let turn = self.turn.cover(-off / settings.turn_rate.to_radians(), seconds, &[1.0]);
+                if turn != 0.0 {
+                    self.settling = SETTLE_UPDATES;
+                }
+                turn
+            },
+            None => self.turn.cover(0.0, seconds, &[]),
+        };
+        let forward = self.forward.update(forward_speed, seconds, &walking);
+        // Walking and running one way while running and walking the other
+        // would take two run keys.
+        let strafe_speeds: &[f32] = if turn != 0.0 {
+            &[]
+        } else if forward == 0.0 {
+            &walking
+        } else if forward.abs() > 1.0 {
+            &walking[1..]
+        } else {
+            &walking[..1]
+        };
+        let strafe = self.strafe.update(strafe_speed, seconds, strafe_speeds);
+        let fly_speeds: &[f32] = if forward.abs() > 1.0 || strafe.abs() > 1.0 {
+            &walking[1..]
+        } else if forward != 0.0 || strafe != 0.0 {
+            &walking[..1]
+        } else {
+            &walking
+        };
+        let fly = self.fly.update(fly_speed, seconds, fly_speeds);
+
+        let mut held = Vec::new();
+        if forward.abs() > 1.0 || strafe.abs() > 1.0 || fly.abs() > 1.0 {
+            held.push(keys.run);
+        }
+        if strafe != 0.0 {
+            held.push(keys.strafe);
+        }
+        if forward > 0.0 {
+            held.push(keys.forward);
+        } else if forward < 0.0 {
+            held.push(keys.back);
+        }
+        if fly > 0.0 {
+            held.push(keys.fly_up);
+        } else if fly < 0.0 {
+            held.push(keys.fly_down);
+        }
+        if turn > 0.0 || strafe > 0.0 {
+            held.push(keys.turn_right);
+        } else if turn < 0.0 || strafe < 0.0 {
+            held.push(keys.turn_left);
+        }
+        held
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: f32 = 1.0 / 90.0;
+
+    fn stick(left: [f32; 2], right: [f32; 2]) -> Frame {
+        Frame {
+            input: InputState { thumbstick: [left, right], ..InputState::default() },
+            head: Pose::identity(),
+            heading: 0.0,
+            seconds: FRAME,
+        }
+    }
+
+    /// How many of `frames` frames hold `key`.
+    fn frames_holding(locomotion: &mut Locomotion, frame: &Frame, frames: usize, key: Key) -> usize {
+        let (settings, keys) = (LocomotionSettings::default(), KeyBindings::default());
+        (0..frames).filter(|_| locomotion.update(frame, &settings, &keys).contains(&key)).count()
+    }
+
+    #[test]
+    fn half_way_walks_half_the_time() {
+        let settings = LocomotionSettings { deadzone: 0.0, run_speed: 2.0, ..LocomotionSettings::default() };
+        let keys = KeyBindings::default();
+        let mut locomotion = Locomotion::new();
+        let frame = stick([0.0, 0.25], [0.0, 0.0]);
+        let held: Vec<Vec<Key>> = (0..90).map(|_| locomotion.update(&frame, &settings, &keys)).collect();
+        let walking = held.iter().filter(|keys| keys.as_slice() == [Key::Up]).count();
+        assert!((44..=46).contains(&walking), "walked {} frames", walking);
+        assert!(held.iter().all(|keys| keys.is_empty() || keys.as_slice() == [Key::Up]));
+    }
+
+    #[test]
+    fn pushing_harder_runs_part_of_the_time() {
+        let settings = LocomotionSettings { deadzone: 0.0, run_speed: 2.0, ..LocomotionSettings::default() };
+        let keys = KeyBindings::default();
+        let mut locomotion = Locomotion::new();
+        let frame = stick([0.0, -0.75], [0.0, 0.0]);
+        let held: Vec<Vec<Key>> = (0..90).map(|_| locomotion.update(&frame, &settings, &keys)).collect();
+        let running = held.iter().filter(|keys| keys.as_slice() == [Key::Control, Key::Down]).count();
+        assert!((44..=46).contains(&running), "ran {} frames", running);
+        assert!(held.iter().skip(1).all(|keys| keys.contains(&Key::Down)));
+    }
+
+    #[test]
+    fn half_way_up_flies_half_the_time() {
+        let settings = LocomotionSettings { deadzone: 0.0, ..LocomotionSettings::default() };
+        let mut locomotion = Locomotion::new();
+        let frame = stick([0.0, 0.0], [0.0, 0.25]);
+        let held: Vec<Vec<Key>> = (0..90).map(|_| locomotion.update(&frame, &settings, &KeyBindings::default())).collect();
+        let flying = held.iter().filter(|keys| keys.as_slice() == [Key::Plus]).count();
+        assert!((44..=46).contains(&flying), "flew {} frames", flying);
+        assert!(held.iter().all(|keys| keys.is_empty() || keys.as_slice() == [Key::Plus]));
+    }
+
+    #[test]
+    fn the_right_stick_either_flies_or_turns() {
+        let mut locomotion = Locomotion::new();
+        let settings = LocomotionSettings::default();
+        let frame = stick([0.0, 0.0], [0.4, -0.9]);
+        for _ in 0..30 {
+            let held = locomotion.update(&frame, &settings, &KeyBindings::default());
+            assert!(!held.contains(&Key::Left) && !held.contains(&Key::Right), "{:?}", held);
+        }
+        assert_eq!(locomotion.yaw(), 0.0);
+        assert!(frames_holding(&mut locomotion, &frame, 30, Key::Minus) > 0);
+
+        let mut locomotion = Locomotion::new();
+        let settings = LocomotionSettings { fly: false, ..LocomotionSettings::default() };
+        let held = locomotion.update(&frame, &settings, &KeyBindings::default());
+        assert!(!held.contains(&Key::Minus));
+        assert!(locomotion.yaw() < 0.0);
+    }
+
+    #[test]
+    fn the_deadzone_stands_still() {
+        let mut locomotion = Locomotion::new();
+        let settings = LocomotionSettings::default();
+        let frame = stick([settings.deadzone * 0.9, -settings.deadzone * 0.9], [settings.deadzone * 0.9, 0.0]);
+        for _ in 0..90 {
+            assert_eq!(locomotion.update(&frame, &settings, &KeyBindings::default()), vec![]);
+        }
+    }
+
+    #[test]
+    fn letting_go_stops_at_once() {
+        let mut locomotion = Locomotion::new();
+        frames_holding(&mut locomotion, &stick([0.0, 0.4], [0.0, 0.0]), 10, Key::Up);
+        assert_eq!(frames_holding(&mut locomotion, &stick([0.0, 0.0], [0.0, 0.0]), 10, Key::Up), 0);
+    }
+
+    #[test]
+    fn the_left_stick_strafes_unless_turning() {
+        let (settings, keys) = (LocomotionSettings::default(), KeyBindings::default());
+        let mut locomotion = Locomotion::new();
+        let strafe = stick([1.0, 0.0], [0.0, 0.0]);
+        for _ in 0..10 {
+            assert_eq!(locomotion.update(&strafe, &settings, &keys), vec![Key::Control, Key::Alt, Key::Right]);
+        }
+        let turning = stick([1.0, 0.0], [-1.0, 0.0]);
+        for _ in 0..10 {
+            assert_eq!(locomotion.update(&turning, &settings, &keys), vec![Key::Left]);
+        }
+    }
+
+    /// Runs `frames` frames, with AW turning the avatar at `turn_rate` while
+    /// a turn key is held. Returns the avatar's heading and the view's yaw
+    /// after each frame.
+    fn turn(locomotion: &mut Locomotion, settings: &LocomotionSettings, frame: &mut Frame, frames: usize) -> Vec<(f32, f32)> {
+        let keys = KeyBindings::default();
+        let mut turned = Vec::new();
+        for _ in 0..frames {
+            let held = locomotion.update(frame, settings, &keys);
+            turned.push((frame.heading, locomotion.yaw()));
+            let turn = settings.turn_rate.to_radians() * FRAME;
+            if held.contains(&Key::Left) {
+                frame.heading += turn;
+            } else if held.contains(&Key::Right) {
+                frame.heading -= turn;
+            }
+        }
+        turned
+    }
+
+    /// Walks for a second with the head and left controller turned `yaw`
+    /// radians.
+    fn walk_looking(reference: LocomotionReference, yaw: f32) -> Vec<(f32, f32)> {
+        let settings = LocomotionSettings { reference, ..LocomotionSettings::default() };
+        let mut frame = stick([0.0, 0.5], [0.0, 0.0]);
+        frame.head = Pose::from_yaw(yaw, [0.0, 1.6, 0.0]);
+        frame.input.hands[0] = Some(Pose::from_yaw(yaw, [0.0, 1.0, -0.3]));
+        turn(&mut Locomotion::new(), &settings, &mut frame, 90)
+    }
+
+    #[test]
+    fn walking_turns_the_avatar_towards_the_head() {
+        let frames = walk_looking(LocomotionReference::Head, 0.5);
+        let (heading, yaw) = frames[frames.len() - 1];
+        assert!((heading - 0.5).abs() < 0.02, "avatar ended up facing {}", heading);
+        // The world stays put in the headset all the way.
+        for &(heading, yaw) in &frames {
+            assert!((heading + yaw).abs() < 1e-4, "view turned {} with the avatar", heading + yaw);
+        }
+        assert!((yaw + 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn walking_turns_the_avatar_towards_the_controller() {
+        let (heading, _) = walk_looking(LocomotionReference::Controller, -1.0)[89];
+        assert!((heading + 1.0).abs() < 0.02, "avatar ended up facing {}", heading);
+    }
+
+    #[test]
+    fn the_avatar_reference_never_turns() {
+        assert!(walk_looking(LocomotionReference::Avatar, 0.5).iter().all(|&frame| frame == (0.0, 0.0)));
+    }
+
+    #[test]
+    fn snaps_turn_the_view_at_once_and_the_avatar_after() {
+        let settings = LocomotionSettings { turning: Turning::Snap, snap_angle: 30.0, ..LocomotionSettings::default() };
+        let mut locomotion = Locomotion::new();
+        let snap = -30f32.to_radians();
+
+        let mut frame = stick([0.0, 0.0], [0.8, 0.0]);
+        let frames = turn(&mut locomotion, &settings, &mut frame, 45);
+        for &(heading, yaw) in &frames {
+            assert!((heading + yaw - snap).abs() < 1e-4, "view faces {} after one snap", heading + yaw);
+        }
+        let (heading, _) = frames[44];
+        assert!((heading - snap).abs() < 0.01, "avatar ended up facing {}", heading);
+
+        // Holding the stick doesn't snap again, but pushing it again does.
+        frame.input.thumbstick[1] = [0.0, 0.0];
+        turn(&mut locomotion, &settings, &mut frame, 1);
+        frame.input.thumbstick[1] = [-1.0, 0.0];
+        let (heading, yaw) = turn(&mut locomotion, &settings, &mut frame, 45)[44];
+        assert!((heading + yaw).abs() < 1e-4, "view faces {} after snapping back", heading + yaw);
+    }
+
+    #[test]
+    fn smooth_turns_go_at_turn_speed() {
+        let settings = LocomotionSettings { deadzone: 0.0, turn_speed: 60.0, ..LocomotionSettings::default() };
+        let mut locomotion = Locomotion::new();
+        let mut frame = stick([0.0, 0.0], [-0.5, 0.0]);
+        let frames = turn(&mut locomotion, &settings, &mut frame, 90);
+        for (index, &(heading, yaw)) in frames.iter().enumerate() {
+            let asked = 30f32.to_radians() * FRAME * (index + 1) as f32;
+            assert!((heading + yaw - asked).abs() < 1e-4, "view faces {} rather than {}", heading + yaw, asked);
+        }
+        let (heading, _) = frames[89];
+        assert!((heading - 30f32.to_radians()).abs() < 0.02, "avatar ended up facing {}", heading);
+    }
+
+    #[test]
+    fn angles_wrap_to_half_a_turn() {
+        assert!((wrap(1.5 * PI) + 0.5 * PI).abs() < 1e-6);
+        assert!((wrap(-1.5 * PI) - 0.5 * PI).abs() < 1e-6);
+        assert_eq!(wrap(-PI), PI);
+        assert_eq!(wrap(0.25), 0.25);
+    }
+}
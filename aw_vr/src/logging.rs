@@ -0,0 +1,285 @@
+//! AW_VR's log: leveled, timestamped lines in `aw_vr.log`, which is rotated
+//! once it grows too big, optionally copied to the debugger and stderr.
+//!
+//! Everything logs through the `log` macros; `init` sets up where it goes.
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{self, Level, LevelFilter, Log, Metadata, Record};
+
+const FILE_STEM: &str = "aw_vr";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogSettings {
+    pub level: LevelFilter,
+    /// Where `aw_vr.log` and the logs it was rotated into go.
+    pub dir: PathBuf,
+    /// How big `aw_vr.log` may get before it's rotated.
+    pub max_bytes: u64,
+    /// How many rotated logs to keep besides `aw_vr.log`.
+    pub keep: usize,
+    /// Also send each line to the debugger with OutputDebugString.
+    pub debugger: bool,
+    /// Also write each line to stderr.
+    pub stderr: bool,
+}
+
+impl Default for LogSettings {
+    fn default() -> LogSettings {
+        LogSettings {
+            level: LevelFilter::Info,
+            dir: PathBuf::from("."),
+            max_bytes: 1024 * 1024,
+            keep: 3,
+            debugger: false,
+            stderr: false,
+        }
+    }
+}
+
+impl LogSettings {
+    /// The defaults, overridden by `AW_VR_LOG_LEVEL` (`off` to `trace`),
+    /// `AW_VR_LOG_DIR`, and `AW_VR_LOG_DEBUGGER` and `AW_VR_LOG_STDERR` set to
+    /// `1`.
+    pub fn from_env() -> LogSettings {
+        let mut settings = LogSettings::default();
+        if let Some(level) = env::var("AW_VR_LOG_LEVEL").ok().and_then(|level| level.parse().ok()) {
+            settings.level = level;
+        }
+        if let Some(dir) = env::var_os("AW_VR_LOG_DIR") {
+            settings.dir = PathBuf::from(dir);
+        }
+        let enabled = |name| env::var(name).map(|value| value == "1").unwrap_or(false);
+        settings.debugger = enabled("AW_VR_LOG_DEBUGGER");
+        settings.stderr = enabled("AW_VR_LOG_STDERR");
+        settings
+    }
+}
+
+/// Starts logging. The debugger and stderr sinks work even if the log file
+/// can't be opened, in which case the error is returned. Only the first call
+/// sets up the sinks; later ones just change the level.
+pub fn init(settings: &LogSettings) -> io::Result<()> {
+    log::set_max_level(settings.level);
+    let (file, result) = match RotatingFile::open(settings.dir.clone(), settings.max_bytes, settings.keep) {
+        Ok(file) => (Some(Mutex::new(file)), Ok(())),
+        Err(error) => (None, Err(error)),
+    };
+    let logger = Logger {
+        file,
+        debugger: settings.debugger,
+        stderr: settings.stderr,
+    };
+    let _ = log::set_boxed_logger(Box::new(logger));
+    result
+}
+
+struct Logger {
+    file: Option<Mutex<RotatingFile>>,
+    debugger: bool,
+    stderr: bool,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format_line(SystemTime::now(), record.level(), record.target(), &record.args().to_string());
+        if let Some(ref file) = self.file {
+            let mut file = file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let _ = file.write_line(&line);
+        }
+        if self.debugger {
+            output_debug_string(&line);
+        }
+        if self.stderr {
+            let _ = io::stderr().write_all(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(ref file) = self.file {
+            let mut file = file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let _ = file.flush();
+        }
+    }
+}
+
+/// One line of the log, newline included.
+fn format_line(time: SystemTime, level: Level, target: &str, message: &str) -> String {
+    format!("{} {:<5} [{}] {}\n", format_timestamp(time), level, target, message)
+}
+
+/// `time` in UTC, to the millisecond: `2018-06-30 21:04:05.250`.
+fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let of_day = seconds % 86_400;
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+        year, month, day, of_day / 3600, of_day / 60 % 60, of_day % 60, since_epoch.subsec_millis())
+}
+
+/// The proleptic Gregorian date `days` days after 1970-01-01, from Howard
+/// Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(windows)]
+fn output_debug_string(line: &str) {
+    use std::ffi::CString;
+    use std::os::raw::c_char;
+
+    extern "system" {
+        fn OutputDebugStringA(output: *const c_char);
+    }
+
+    if let Ok(line) = CString::new(line) {
+        unsafe {
+            OutputDebugStringA(line.as_ptr());
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn output_debug_string(_line: &str) {}
+
+/// `aw_vr.log`, which is renamed to `aw_vr.1.log` once it's full, pushing
+/// `aw_vr.1.log` to `aw_vr.2.log` and so on, and the oldest out.
+struct RotatingFile {
+    dir: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    file: Option<File>,
+    written: u64,
+}
+
+impl RotatingFile {
+    fn open(dir: PathBuf, max_bytes: u64, keep: usize) -> io::Result<RotatingFile> {
+        fs::create_dir_all(&dir)?;
+        let mut file = RotatingFile { dir, max_bytes, keep, file: None, written: 0 };
+        let opened = OpenOptions::new().create(true).append(true).open(file.path(0))?;
+        file.written = opened.metadata()?.len();
+        file.file = Some(opened);
+        Ok(file)
+    }
+
+    /// `aw_vr.log` for 0, otherwise the `index`th most recent rotated log.
+    fn path(&self, index: usize) -> PathBuf {
+        if index == 0 {
+            self.dir.join(format!("{}.log", FILE_STEM))
+        } else {
+            self.dir.join(format!("{}.{}.log", FILE_STEM, index))
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.written > 0 && self.written + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        match self.file {
+            Some(ref mut file) => file.write_all(line.as_bytes())?,
+            None => return Ok(()),
+        }
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        let ignore_missing = |result: io::Result<()>| match result {
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        };
+        if self.keep == 0 {
+            ignore_missing(fs::remove_file(self.path(0)))?;
+        } else {
+            for index in (0..self.keep).rev() {
+                ignore_missing(fs::rename(self.path(index), self.path(index + 1)))?;
+            }
+        }
+        self.file = Some(File::create(self.path(0))?);
+        self.written = 0;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.file {
+            Some(ref mut file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+    use std::time::Duration;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("aw_vr_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn lines_are_timestamped_in_utc() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_530_392_645_250);
+        assert_eq!(format_line(time, Level::Warn, "aw_vr::stereo", "eye lost"),
+            "2018-06-30 21:04:05.250 WARN  [aw_vr::stereo] eye lost\n");
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01 00:00:00.000");
+        assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)), "2000-02-29 00:00:00.000");
+    }
+
+    #[test]
+    fn full_logs_are_rotated_and_the_oldest_dropped() {
+        let dir = temp_dir("rotation");
+        let mut file = RotatingFile::open(dir.clone(), 10, 2).unwrap();
+        for line in &["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_line(line).unwrap();
+        }
+        drop(file);
+
+        let read = |name: &str| fs::read_to_string(dir.join(name)).ok();
+        assert_eq!(read("aw_vr.log").as_deref(), Some("fourth\n"));
+        assert_eq!(read("aw_vr.1.log").as_deref(), Some("third\n"));
+        assert_eq!(read("aw_vr.2.log").as_deref(), Some("second\n"));
+        assert_eq!(read("aw_vr.3.log"), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reopening_appends() {
+        let dir = temp_dir("append");
+        RotatingFile::open(dir.clone(), 100, 1).unwrap().write_line("before\n").unwrap();
+        let mut file = RotatingFile::open(dir.clone(), 100, 1).unwrap();
+        assert_eq!(file.written, 7);
+        file.write_line("after\n").unwrap();
+        drop(file);
+
+        assert_eq!(fs::read_to_string(dir.join("aw_vr.log")).unwrap(), "before\nafter\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+use std::os::raw::c_void;
+
+use backend::Fov;
+use error::AwVrError;
+use lib;
+
+lazy_static! {
+    static ref RW: Result<lib::Library, AwVrError> = lib::Library::new("rw_opengl")
+        .map_err(|error| AwVrError::Library { library: "rw_opengl", reason: error.to_string() });
+    static ref Functions: Result<RenderWare, AwVrError> = RenderWare::load();
+}
+
+/// The rw_opengl functions AW_VR calls but doesn't hook.
+#[allow(non_snake_case)]
+pub struct RenderWare {
+    pub rw_frame_translate: extern "C" fn(*mut c_void, *mut f32, u32) -> *mut c_void,
+    pub rw_frame_rotate: extern "C" fn(*mut c_void, *mut f32, f32, u32) -> *mut c_void,
+    pub rw_camera_set_view_offset: extern "C" fn(*mut c_void, *mut f32) -> *mut c_void,
+    pub rw_frame_get_matrix: extern "C" fn(*mut c_void) -> *mut c_void,
+    pub rw_matrix_create: extern "C" fn() -> *mut c_void,
+    pub rw_matrix_copy: extern "C" fn(*mut c_void, *mut c_void) -> *mut c_void,
+    /// Not always exported; without them no depth is submitted.
+    pub rw_camera_get_near_clip_plane: Option<extern "C" fn(*mut c_void) -> f32>,
+    pub rw_camera_get_far_clip_plane: Option<extern "C" fn(*mut c_void) -> f32>,
+}
+
+/// `name` must be NUL-terminated, and `T` the function's type.
+unsafe fn symbol<T: Copy>(library: &lib::Library, name: &'static str) -> Result<T, AwVrError> {
+    library.get::<T>(name.as_bytes())
+        .map(|symbol| *symbol)
+        .map_err(|_| AwVrError::Symbol { library: "rw_opengl", symbol: &name[..name.len() - 1] })
+}
+
+impl RenderWare {
+    fn load() -> Result<RenderWare, AwVrError> {
+        let library = RW.as_ref().map_err(Clone::clone)?;
+        unsafe {
+            Ok(RenderWare {
+                rw_frame_translate: symbol(library, "rw_frame_translate\0")?,
+                rw_frame_rotate: symbol(library, "rw_frame_rotate\0")?,
+                rw_camera_set_view_offset: symbol(library, "rw_camera_set_view_offset\0")?,
+                rw_frame_get_matrix: symbol(library, "rw_frame_get_matrix\0")?,
+                rw_matrix_create: symbol(library, "rw_matrix_create\0")?,
+                rw_matrix_copy: symbol(library, "rw_matrix_copy\0")?,
+                rw_camera_get_near_clip_plane: symbol(library, "rw_camera_get_near_clip_plane\0").ok(),
+                rw_camera_get_far_clip_plane: symbol(library, "rw_camera_get_far_clip_plane\0").ok(),
+            })
+        }
+    }
+
+    /// The camera's near and far clip planes, in RenderWare units, if this
+    /// build of rw_opengl can say.
+    pub fn camera_clip_planes(&self, camera: *mut c_void) -> Option<(f32, f32)> {
+        match (self.rw_camera_get_near_clip_plane, self.rw_camera_get_far_clip_plane) {
+            (Some(near), Some(far)) => Some((near(camera), far(camera))),
+            _ => None,
+        }
+    }
+}
+
+/// rw_opengl's functions, loading them on first use.
+pub fn functions() -> Result<&'static RenderWare, AwVrError> {
+    Functions.as_ref().map_err(Clone::clone)
+}
+
+pub fn camera_get_frame(camera: *mut c_void) -> *mut c_void {
+    unsafe {
+        let camera_as_ptrs = camera as *mut usize;
+        let ptr_to_frame = camera_as_ptrs.offset(1);
+        *ptr_to_frame as *mut c_void
+    }
+}
+
+/// Which way a frame matrix faces, in radians counter-clockwise seen from
+/// above, 0 being along +z.
+pub fn matrix_heading(matrix: *mut c_void) -> f32 {
+    unsafe {
+        // An RwMatrix is its right, up, at and pos vectors, each padded to
+        // four floats.
+        let at = (matrix as *const f32).offset(8);
+        (*at).atan2(*at.offset(2))
+    }
+}
+
+/// An eye's frustum in RenderWare's terms.
+///
+/// RenderWare centers the view window on the camera's axis one unit in front of
+/// it, and puts the eye `offset` away from that axis. Camera-space x points to
+/// the left of the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewWindow {
+    /// Half the window's width and height.
+    pub window: [f32; 2],
+    pub offset: [f32; 2],
+}
+
+impl ViewWindow {
+    pub fn from_fov(fov: Fov) -> ViewWindow {
+        ViewWindow {
+            window: [(fov.left + fov.right) / 2.0, (fov.up + fov.down) / 2.0],
+            offset: [(fov.right - fov.left) / 2.0, (fov.down - fov.up) / 2.0],
+        }
+    }
+
+    /// Where the eye's frame has to move so the eye, rather than the
+    /// window's center, ends up at the tracked position.
+    pub fn eye_correction(&self) -> [f32; 3] {
+        [-self.offset[0], -self.offset[1], 0.0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symmetric_fov_is_centered() {
+        let view = ViewWindow::from_fov(Fov::square());
+        assert_eq!(view, ViewWindow { window: [1.0, 1.0], offset: [0.0, 0.0] });
+    }
+
+    #[test]
+    fn asymmetric_fov_moves_the_eye_off_center() {
+        // A left eye that sees further towards the nose than away from it.
+        let view = ViewWindow::from_fov(Fov { up: 1.25, down: 1.5, left: 1.0, right: 1.5 });
+        assert_eq!(view.window, [1.25, 1.375]);
+        // The window's center is right of the eye, so the eye sits screen-left of it, on +x.
+        assert_eq!(view.offset, [0.25, 0.125]);
+        assert_eq!(view.eye_correction(), [-0.25, -0.125, 0.0]);
+    }
+}
@@ -0,0 +1,292 @@
+//! The per-frame state machine behind the RenderWare camera hooks.
+//!
+//! AW draws its world camera once per frame, and AW_VR spends alternate draws
+//! on alternate eyes; other cameras are passed over. The left eye's draw
+//! starts a headset frame and fixes the poses both eyes use; each draw is then
+//! copied into its eye's swap chain, and the right eye's draw hands the frame
+//! to the compositor. Eyes are drawn at the size the runtime recommends,
+//! whatever size AW's window is.
+
+use backend::{Error, Eye, EyeTextures, Fov, InputState, Pose, VrBackend};
+use cameras::CameraTracker;
+
+/// What a camera draw needs to know to render its eye.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EyePass {
+    pub eye: Eye,
+    /// Unscaled, in meters from the tracking origin.
+    pub pose: Pose,
+    pub fov: Fov,
+    /// The eye buffer size to draw at, or `None` if the headset doesn't want
+    /// this frame and AW should draw to its window as usual.
+    pub size: Option<(u32, u32)>,
+    pub input: InputState,
+    /// Whether the runtime recentered the headset this frame. Only ever set
+    /// for the left eye.
+    pub recentered: bool,
+}
+
+pub struct Stereo {
+    cameras: CameraTracker,
+    rendering: bool,
+    poses: [Pose; 2],
+    fovs: [Fov; 2],
+    pixel_density: f32,
+    size: Option<(u32, u32)>,
+}
+
+impl Stereo {
+    /// `pixel_density` scales the runtime's recommended eye buffer size.
+    pub fn new(pixel_density: f32) -> Stereo {
+        Stereo {
+            cameras: CameraTracker::new(),
+            rendering: false,
+            poses: [Pose::identity(), Pose::identity()],
+            fovs: [Fov::square(), Fov::square()],
+            pixel_density,
+            size: None,
+        }
+    }
+
+    /// Marks `camera` as AW's world camera.
+    pub fn set_main_camera(&mut self, camera: usize) {
+        self.cameras.set_main(camera);
+    }
+
+    /// AW's world camera, if it's known yet.
+    pub fn main_camera(&self) -> Option<usize> {
+        self.cameras.main()
+    }
+
+    /// Called as AW starts drawing with `camera`. Returns `None` unless it's
+    /// the world camera. The left eye begins a new headset frame, creating the
+    /// swap chains first if there are none.
+    pub fn begin_eye(&mut self, backend: &mut dyn VrBackend, camera: usize) -> Result<Option<EyePass>, Error> {
+        let eye = match self.cameras.begin(camera) {
+            Some(eye) => eye,
+            None => return Ok(None),
+        };
+        let input = backend.poll_input()?;
+        let mut recentered = false;
+        if eye == Eye::Left {
+            let start = backend.begin_frame()?;
+            self.rendering = start.render;
+            recentered = start.recentered;
+            self.poses = backend.eye_poses()?;
+            self.fovs = [backend.eye_fov(Eye::Left)?, backend.eye_fov(Eye::Right)?];
+            if self.rendering && self.size.is_none() {
+                let (width, height) = backend.recommended_size(self.pixel_density)?;
+                backend.create_swap_chains(width, height)?;
+                info!("created {}x{} {} swap chains", width, height, backend.name());
+                self.size = Some((width, height));
+            }
+        }
+        Ok(Some(EyePass {
+            eye,
+            pose: self.poses[eye.index()],
+            fov: self.fovs[eye.index()],
+            size: if self.rendering { self.size } else { None },
+            input,
+            recentered,
+        }))
+    }
+
+    /// Called once AW has drawn with `camera`, returning the eye drawn if it
+    /// was the world camera. If the headset wants this frame, `copy` is given
+    /// the eye's textures to copy the drawing into. The right eye then submits
+    /// the frame. If `copy` fails, the eye isn't committed.
+    pub fn end_eye<C, E>(&mut self, backend: &mut dyn VrBackend, camera: usize, copy: C) -> Result<Option<Eye>, E>
+        where C: FnOnce(EyeTextures) -> Result<(), E>, E: From<Error>
+    {
+        let eye = match self.cameras.end(camera) {
+            Some(eye) => eye,
+            None => return Ok(None),
+        };
+        if self.rendering {
+            let textures = backend.acquire_eye_textures(eye)?;
+            copy(textures)?;
+            backend.commit(eye)?;
+        }
+        if eye == Eye::Right {
+            backend.submit()?;
+        }
+        Ok(Some(eye))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::button;
+    use backend::mock::{Call, MockBackend};
+
+    fn pose(x: f32) -> Pose {
+        Pose { position: [x, 1.6, 0.0], orientation: [0.0, 0.0, 0.0, 1.0] }
+    }
+
+    const WORLD: usize = 0x1000;
+    const MINIMAP: usize = 0x2000;
+
+    /// Draws one eye, returning its pass and the textures it was copied into.
+    fn draw(stereo: &mut Stereo, backend: &mut MockBackend) -> (EyePass, Option<EyeTextures>) {
+        let pass = stereo.begin_eye(backend, WORLD).unwrap().unwrap();
+        let mut copied = None;
+        let eye = stereo.end_eye(backend, WORLD, |textures| {
+            copied = Some(textures);
+            Ok::<_, Error>(())
+        });
+        assert_eq!(eye.unwrap(), Some(pass.eye));
+        (pass, copied)
+    }
+
+    #[test]
+    fn alternate_draws_make_one_stereo_frame() {
+        let mut backend = MockBackend::new();
+        let mut stereo = Stereo::new(1.0);
+
+        let (left, left_texture) = draw(&mut stereo, &mut backend);
+        let (right, right_texture) = draw(&mut stereo, &mut backend);
+
+        assert_eq!((left.eye, right.eye), (Eye::Left, Eye::Right));
+        assert!(left_texture.is_some() && right_texture.is_some());
+        assert_eq!(backend.take_calls(), vec![
+            Call::PollInput,
+            Call::BeginFrame,
+            Call::EyePoses,
+            Call::EyeFov(Eye::Left),
+            Call::EyeFov(Eye::Right),
+            Call::RecommendedSize,
+            Call::CreateSwapChains(1000, 1100),
+            Call::Acquire(Eye::Left),
+            Call::Commit(Eye::Left),
+            Call::PollInput,
+            Call::Acquire(Eye::Right),
+            Call::Commit(Eye::Right),
+            Call::Submit,
+        ]);
+    }
+
+    #[test]
+    fn both_eyes_use_the_poses_fetched_for_the_left() {
+        let mut backend = MockBackend::new();
+        backend.poses.push_back([pose(-0.03), pose(0.03)]);
+        backend.poses.push_back([pose(1.0), pose(1.1)]);
+        let mut stereo = Stereo::new(1.0);
+
+        assert_eq!(draw(&mut stereo, &mut backend).0.pose, pose(-0.03));
+        assert_eq!(draw(&mut stereo, &mut backend).0.pose, pose(0.03));
+        assert_eq!(draw(&mut stereo, &mut backend).0.pose, pose(1.0));
+        assert_eq!(draw(&mut stereo, &mut backend).0.pose, pose(1.1));
+        assert_eq!(backend.calls.iter().filter(|&&call| call == Call::EyePoses).count(), 2);
+    }
+
+    #[test]
+    fn each_eye_gets_its_own_fov() {
+        let mut backend = MockBackend::new();
+        let left = Fov { up: 1.2, down: 1.3, left: 1.1, right: 0.9 };
+        let right = Fov { up: 1.2, down: 1.3, left: 0.9, right: 1.1 };
+        backend.fov = [left, right];
+        let mut stereo = Stereo::new(1.0);
+
+        assert_eq!(draw(&mut stereo, &mut backend).0.fov, left);
+        assert_eq!(draw(&mut stereo, &mut backend).0.fov, right);
+    }
+
+    #[test]
+    fn depth_is_copied_when_the_runtime_takes_it() {
+        let mut backend = MockBackend::new();
+        let mut stereo = Stereo::new(1.0);
+        assert_eq!(draw(&mut stereo, &mut backend).1.unwrap().depth, None);
+
+        backend.depth = true;
+        let textures = draw(&mut stereo, &mut backend).1.unwrap();
+        assert!(textures.depth.is_some() && textures.depth != Some(textures.color));
+    }
+
+    #[test]
+    fn a_failed_copy_isnt_committed() {
+        let mut backend = MockBackend::new();
+        let mut stereo = Stereo::new(1.0);
+        stereo.begin_eye(&mut backend, WORLD).unwrap();
+        backend.take_calls();
+
+        let failure = Error::Call { call: "copy", code: 1 };
+        assert_eq!(stereo.end_eye(&mut backend, WORLD, |_| Err(failure.clone())), Err(failure));
+        assert_eq!(backend.take_calls(), vec![Call::Acquire(Eye::Left)]);
+    }
+
+    #[test]
+    fn skipped_frames_are_still_submitted() {
+        let mut backend = MockBackend::new();
+        backend.should_render = false;
+        let mut stereo = Stereo::new(1.0);
+
+        assert_eq!(draw(&mut stereo, &mut backend).1, None);
+        assert_eq!(draw(&mut stereo, &mut backend).1, None);
+        let calls = backend.take_calls();
+        assert!(!calls.iter().any(|call| matches!(call, Call::Acquire(_) | Call::Commit(_) | Call::CreateSwapChains(..))));
+        assert_eq!(calls.last(), Some(&Call::Submit));
+    }
+
+    #[test]
+    fn eyes_are_drawn_at_the_recommended_size() {
+        let mut backend = MockBackend::new();
+        let mut stereo = Stereo::new(1.5);
+
+        assert_eq!(draw(&mut stereo, &mut backend).0.size, Some((1500, 1650)));
+        assert_eq!(draw(&mut stereo, &mut backend).0.size, Some((1500, 1650)));
+        assert_eq!(draw(&mut stereo, &mut backend).0.size, Some((1500, 1650)));
+        assert_eq!(backend.calls.iter().filter(|call| matches!(call, Call::CreateSwapChains(..))).count(), 1);
+    }
+
+    #[test]
+    fn skipped_frames_are_drawn_to_the_window() {
+        let mut backend = MockBackend::new();
+        backend.should_render = false;
+        let mut stereo = Stereo::new(1.0);
+
+        assert_eq!(draw(&mut stereo, &mut backend).0.size, None);
+        backend.should_render = true;
+        draw(&mut stereo, &mut backend);
+        assert_eq!(draw(&mut stereo, &mut backend).0.size, Some((1000, 1100)));
+    }
+
+    #[test]
+    fn input_is_passed_on_without_acting_on_it() {
+        let mut backend = MockBackend::new();
+        backend.input.push_back(InputState { buttons: button::ENTER, ..InputState::default() });
+        let mut stereo = Stereo::new(1.0);
+
+        let (pass, _) = draw(&mut stereo, &mut backend);
+
+        assert_eq!(pass.input.buttons, button::ENTER);
+        assert_eq!(backend.calls[0], Call::PollInput);
+        assert!(!backend.calls.contains(&Call::Recenter));
+    }
+
+    #[test]
+    fn runtime_recenters_are_passed_on_once() {
+        let mut backend = MockBackend::new();
+        backend.recentered = true;
+        let mut stereo = Stereo::new(1.0);
+
+        assert!(draw(&mut stereo, &mut backend).0.recentered);
+        assert!(!draw(&mut stereo, &mut backend).0.recentered);
+        assert!(!draw(&mut stereo, &mut backend).0.recentered);
+    }
+
+    #[test]
+    fn other_cameras_dont_touch_the_headset() {
+        let mut backend = MockBackend::new();
+        let mut stereo = Stereo::new(1.0);
+        stereo.set_main_camera(WORLD);
+
+        assert_eq!(stereo.begin_eye(&mut backend, MINIMAP).unwrap(), None);
+        assert_eq!(stereo.end_eye(&mut backend, MINIMAP, |_| -> Result<(), Error> { panic!("minimap copied") }).unwrap(), None);
+        assert!(backend.calls.is_empty());
+        assert_eq!(draw(&mut stereo, &mut backend).0.eye, Eye::Left);
+        assert_eq!(stereo.begin_eye(&mut backend, MINIMAP).unwrap(), None);
+        assert_eq!(stereo.end_eye(&mut backend, MINIMAP, |_| -> Result<(), Error> { panic!("minimap copied") }).unwrap(), None);
+        assert_eq!(draw(&mut stereo, &mut backend).0.eye, Eye::Right);
+    }
+}
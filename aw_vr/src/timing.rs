@@ -0,0 +1,301 @@
+//! Frame pacing: numbering headset frames and measuring how long they take.
+//!
+//! LibOVR predicts the display time of a frame from its index, and wants the
+//! same index back on submission along with when the sensors were read for it.
+//! `FramePacer` hands out the indices, keeps each frame's timing, and sums up
+//! frame times and latency so they can be reported.
+
+use std::time::Instant;
+
+/// A source of time in seconds. Only differences between readings matter.
+pub trait Clock: Send {
+    fn now(&self) -> f64;
+}
+
+/// For runtimes without a clock of their own.
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        let elapsed = self.start.elapsed();
+        elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9
+    }
+}
+
+/// The timing of one frame, in the pacer's clock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameTiming {
+    pub index: u64,
+    pub began: f64,
+    /// When the runtime expects the frame to be shown, if it said.
+    pub predicted_display: Option<f64>,
+    /// When the poses the frame is drawn from were read.
+    pub sensor_sample: Option<f64>,
+}
+
+/// Frame statistics over some stretch of frames. Times are in seconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    /// How many frames were submitted.
+    pub frames: u32,
+    /// Time from one frame beginning to the next.
+    pub mean_frame_time: f64,
+    pub max_frame_time: f64,
+    /// Time from reading the sensors to submitting the frame.
+    pub mean_render_latency: Option<f64>,
+    /// Time from reading the sensors to the predicted display time, which is
+    /// the motion-to-photon latency the runtime is expecting.
+    pub mean_display_latency: Option<f64>,
+}
+
+impl FrameStats {
+    pub fn frames_per_second(&self) -> f64 {
+        if self.mean_frame_time > 0.0 {
+            1.0 / self.mean_frame_time
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Mean {
+    sum: f64,
+    count: u32,
+}
+
+impl Mean {
+    fn add(&mut self, value: f64) {
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn get(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.sum / f64::from(self.count))
+        }
+    }
+}
+
+pub struct FramePacer {
+    clock: Box<dyn Clock>,
+    /// The frame in progress, or the last one if it has ended.
+    frame: Option<FrameTiming>,
+    ended: bool,
+    /// How long after the previous frame the one in progress began.
+    frame_time: Option<f64>,
+    frames: u32,
+    frame_times: Mean,
+    max_frame_time: f64,
+    render_latency: Mean,
+    display_latency: Mean,
+}
+
+impl FramePacer {
+    pub fn new(clock: Box<dyn Clock>) -> FramePacer {
+        FramePacer {
+            clock,
+            frame: None,
+            ended: true,
+            frame_time: None,
+            frames: 0,
+            frame_times: Mean::default(),
+            max_frame_time: 0.0,
+            render_latency: Mean::default(),
+            display_latency: Mean::default(),
+        }
+    }
+
+    /// Starts a new frame and returns its index. Indices start at 1 and only go
+    /// up, even past frames that never ended.
+    pub fn begin_frame(&mut self) -> u64 {
+        let now = self.clock.now();
+        let index = self.frame.map_or(1, |frame| frame.index + 1);
+        self.frame_time = self.frame.map(|frame| now - frame.began);
+        self.frame = Some(FrameTiming {
+            index,
+            began: now,
+            predicted_display: None,
+            sensor_sample: None,
+        });
+        self.ended = false;
+        index
+    }
+
+    /// The frame in progress, if there is one.
+    pub fn current(&self) -> Option<&FrameTiming> {
+        if self.ended {
+            None
+        } else {
+            self.frame.as_ref()
+        }
+    }
+
+    /// The index of the frame in progress, or of the last one. 0 before any.
+    pub fn frame_index(&self) -> u64 {
+        self.frame.map_or(0, |frame| frame.index)
+    }
+
+    pub fn set_predicted_display_time(&mut self, time: f64) {
+        if let Some(frame) = self.frame.as_mut() {
+            frame.predicted_display = Some(time);
+        }
+    }
+
+    /// Records that the frame's poses are being read now, returning the time.
+    pub fn sample_sensors(&mut self) -> f64 {
+        let now = self.clock.now();
+        if let Some(frame) = self.frame.as_mut() {
+            frame.sensor_sample = Some(now);
+        }
+        now
+    }
+
+    /// Ends the frame in progress once it has been submitted, returning its
+    /// timing.
+    pub fn end_frame(&mut self) -> Option<FrameTiming> {
+        if self.ended {
+            return None;
+        }
+        self.ended = true;
+        let frame = self.frame?;
+        let now = self.clock.now();
+        self.frames += 1;
+        if let Some(frame_time) = self.frame_time {
+            self.frame_times.add(frame_time);
+            self.max_frame_time = self.max_frame_time.max(frame_time);
+        }
+        if let Some(sensor_sample) = frame.sensor_sample {
+            self.render_latency.add(now - sensor_sample);
+            if let Some(display) = frame.predicted_display {
+                self.display_latency.add(display - sensor_sample);
+            }
+        }
+        Some(frame)
+    }
+
+    /// Statistics for the frames ended since this was last called.
+    pub fn take_stats(&mut self) -> FrameStats {
+        let stats = FrameStats {
+            frames: self.frames,
+            mean_frame_time: self.frame_times.get().unwrap_or(0.0),
+            max_frame_time: self.max_frame_time,
+            mean_render_latency: self.render_latency.get(),
+            mean_display_latency: self.display_latency.get(),
+        };
+        self.frames = 0;
+        self.frame_times = Mean::default();
+        self.max_frame_time = 0.0;
+        self.render_latency = Mean::default();
+        self.display_latency = Mean::default();
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[derive(Clone)]
+    struct FakeClock(Arc<Mutex<f64>>);
+
+    impl FakeClock {
+        fn advance(&self, seconds: f64) {
+            *self.0.lock().unwrap() += seconds;
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> f64 {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn pacer() -> (FramePacer, FakeClock) {
+        let clock = FakeClock(Arc::new(Mutex::new(100.0)));
+        (FramePacer::new(Box::new(clock.clone())), clock)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} isn't {}", actual, expected);
+    }
+
+    #[test]
+    fn frame_indices_only_go_up() {
+        let (mut pacer, _) = pacer();
+        assert_eq!(pacer.frame_index(), 0);
+        assert_eq!(pacer.begin_frame(), 1);
+        assert_eq!(pacer.end_frame().unwrap().index, 1);
+        assert_eq!(pacer.frame_index(), 1);
+        // A frame that never ends still uses up its index.
+        assert_eq!(pacer.begin_frame(), 2);
+        assert_eq!(pacer.begin_frame(), 3);
+        assert_eq!(pacer.end_frame().unwrap().index, 3);
+        assert_eq!(pacer.end_frame(), None);
+    }
+
+    #[test]
+    fn a_frame_keeps_its_sensor_and_display_times() {
+        let (mut pacer, clock) = pacer();
+        pacer.begin_frame();
+        pacer.set_predicted_display_time(100.05);
+        clock.advance(0.002);
+        assert_close(pacer.sample_sensors(), 100.002);
+        assert_eq!(pacer.current().unwrap().sensor_sample, Some(100.002));
+
+        let frame = pacer.end_frame().unwrap();
+        assert_eq!(frame.began, 100.0);
+        assert_eq!(frame.predicted_display, Some(100.05));
+        assert_eq!(frame.sensor_sample, Some(100.002));
+        assert_eq!(pacer.current(), None);
+    }
+
+    #[test]
+    fn stats_cover_frames_since_they_were_last_taken() {
+        let (mut pacer, clock) = pacer();
+        for &frame_time in &[0.010, 0.012, 0.014] {
+            pacer.begin_frame();
+            pacer.set_predicted_display_time(clock.now() + 0.030);
+            pacer.sample_sensors();
+            clock.advance(0.008);
+            pacer.end_frame();
+            clock.advance(frame_time - 0.008);
+        }
+
+        let stats = pacer.take_stats();
+        assert_eq!(stats.frames, 3);
+        // The first frame has nothing before it to be timed against.
+        assert_close(stats.mean_frame_time, 0.011);
+        assert_close(stats.max_frame_time, 0.012);
+        assert_close(stats.frames_per_second(), 1.0 / 0.011);
+        assert_close(stats.mean_render_latency.unwrap(), 0.008);
+        assert_close(stats.mean_display_latency.unwrap(), 0.030);
+
+        assert_eq!(pacer.take_stats(), FrameStats::default());
+    }
+
+    #[test]
+    fn latency_is_unknown_without_a_sensor_sample() {
+        let (mut pacer, _) = pacer();
+        pacer.begin_frame();
+        pacer.set_predicted_display_time(100.05);
+        pacer.end_frame();
+
+        let stats = pacer.take_stats();
+        assert_eq!(stats.frames, 1);
+        assert_eq!(stats.mean_render_latency, None);
+        assert_eq!(stats.mean_display_latency, None);
+    }
+}
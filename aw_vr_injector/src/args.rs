@@ -0,0 +1,295 @@
+//! The injector's command line.
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Injects aw_vr.dll into a running Active Worlds, or starts one with it.
+
+Usage: aw_vr_injector [options]
+       aw_vr_injector [options] --launch <EXE> [-- <AW arguments>...]
+       aw_vr_injector --eject [-p <PID> | -n <PATTERN>]
+
+Options:
+  -L, --launch <EXE>       Start this Active Worlds, in its own directory, with
+                           aw_vr.dll injected before it runs. Arguments after --
+                           are passed to it
+  -p, --pid <PID>          Inject into this process
+  -n, --process <PATTERN>  Inject into the one process whose name matches; * and ?
+                           are wildcards [default: aworld.exe]
+  -x, --eject              Take aw_vr.dll back out of the process, leaving VR off
+  -d, --dll <PATH>         The DLL to inject [default: aw_vr.dll next to the injector]
+  -c, --config <PATH>      The config for aw_vr.dll to use instead of the aw_vr.toml
+                           next to it
+  -e, --embed-config       Send the config's contents instead of its path. It won't
+                           be reloaded when it changes
+      --log-dir <DIR>      Where aw_vr.dll logs [default: AW's directory]
+      --log-debugger       Also send aw_vr.dll's log to the debugger
+      --openxr             Use OpenXR instead of LibOVR
+  -l, --list               List the processes that match instead of injecting
+  -v, --verbose            Say more about what's happening
+  -q, --quiet              Only print errors
+  -h, --help               Print this and exit
+  -V, --version            Print the injector's version and exit
+
+Exit codes:
+  0  Injected, asked to eject, or listed at least one process
+  1  EasyHook failed to inject or launch, or the eject request failed
+  2  Bad command line
+  3  No process matched
+  4  More than one process matched
+  5  The DLL, config or executable doesn't exist
+  6  aw_vr.dll isn't in the process to eject it from";
+
+pub const DEFAULT_PROCESS: &str = "aworld.exe";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Inject,
+    Launch,
+    Eject,
+    List,
+    Help,
+    Version,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Args {
+    pub mode: Mode,
+    pub pid: Option<usize>,
+    pub process: String,
+    /// What to start with `--launch`, and its arguments.
+    pub launch: Option<PathBuf>,
+    pub launch_args: Vec<String>,
+    pub dll: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    pub embed_config: bool,
+    pub log_dir: Option<PathBuf>,
+    pub log_debugger: bool,
+    pub openxr: bool,
+    pub verbosity: Verbosity,
+}
+
+impl Default for Args {
+    fn default() -> Args {
+        Args {
+            mode: Mode::Inject,
+            pid: None,
+            process: DEFAULT_PROCESS.to_owned(),
+            launch: None,
+            launch_args: Vec::new(),
+            dll: None,
+            config: None,
+            embed_config: false,
+            log_dir: None,
+            log_debugger: false,
+            openxr: false,
+            verbosity: Verbosity::Normal,
+        }
+    }
+}
+
+/// Parses the arguments after the program name. Options taking a value accept
+/// it as the next argument or after `=`. Everything after `--` is for AW.
+pub fn parse<I: IntoIterator<Item = OsString>>(args: I) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut mode = None;
+    let mut verbosity = None;
+    let mut process = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let arg = arg.into_string().map_err(|arg| format!("{} isn't valid Unicode", arg.to_string_lossy()))?;
+        if arg == "--" {
+            parsed.launch_args = args.by_ref()
+                .map(|arg| arg.into_string().map_err(|arg| format!("{} isn't valid Unicode", arg.to_string_lossy())))
+                .collect::<Result<_, _>>()?;
+            break;
+        }
+        let (name, mut value) = match arg.find('=') {
+            Some(index) if arg.starts_with("--") => (arg[..index].to_owned(), Some(arg[index + 1..].to_owned())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |args: &mut I::IntoIter| -> Result<String, String> {
+            match value.take() {
+                Some(value) => Ok(value),
+                None => args.next()
+                    .map(|value| value.to_string_lossy().into_owned())
+                    .ok_or_else(|| format!("{} needs a value", name)),
+            }
+        };
+        let mut set_verbosity = |level| match verbosity {
+            Some(other) if other != level => Err("--verbose and --quiet can't be used together".to_owned()),
+            _ => {
+                verbosity = Some(level);
+                Ok(())
+            },
+        };
+        let mut set_mode = |chosen| match mode {
+            Some(other) if other != chosen => Err("only one of --launch, --list and --eject can be used".to_owned()),
+            _ => {
+                mode = Some(chosen);
+                Ok(())
+            },
+        };
+        match &*name {
+            "-p" | "--pid" => {
+                let pid = value(&mut args)?;
+                parsed.pid = Some(pid.parse().map_err(|_| format!("{} isn't a process id", pid))?);
+            },
+            "-n" | "--process" => process = Some(value(&mut args)?),
+            "-L" | "--launch" => {
+                parsed.launch = Some(PathBuf::from(value(&mut args)?));
+                set_mode(Mode::Launch)?;
+            },
+            "-d" | "--dll" => parsed.dll = Some(PathBuf::from(value(&mut args)?)),
+            "-c" | "--config" => parsed.config = Some(PathBuf::from(value(&mut args)?)),
+            "-e" | "--embed-config" => parsed.embed_config = true,
+            "--log-dir" => parsed.log_dir = Some(PathBuf::from(value(&mut args)?)),
+            "--log-debugger" => parsed.log_debugger = true,
+            "--openxr" => parsed.openxr = true,
+            "-x" | "--eject" => set_mode(Mode::Eject)?,
+            "-l" | "--list" => set_mode(Mode::List)?,
+            "-v" | "--verbose" => set_verbosity(Verbosity::Verbose)?,
+            "-q" | "--quiet" => set_verbosity(Verbosity::Quiet)?,
+            "-h" | "--help" => return Ok(Args { mode: Mode::Help, ..parsed }),
+            "-V" | "--version" => return Ok(Args { mode: Mode::Version, ..parsed }),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    parsed.mode = mode.unwrap_or(Mode::Inject);
+    if parsed.launch.is_some() && (parsed.pid.is_some() || process.is_some()) {
+        return Err("--launch starts a new process, so it can't be used with --pid or --process".to_owned());
+    }
+    if parsed.mode == Mode::Eject && (parsed.dll.is_some() || parsed.config.is_some() || parsed.log_dir.is_some() || parsed.log_debugger || parsed.openxr) {
+        return Err("--eject only takes --pid or --process".to_owned());
+    }
+    if parsed.mode != Mode::Launch && !parsed.launch_args.is_empty() {
+        return Err("arguments after -- are only for --launch".to_owned());
+    }
+    if parsed.mode == Mode::List && parsed.pid.is_some() {
+        return Err("--list picks processes by --process, not --pid".to_owned());
+    }
+    if parsed.embed_config && parsed.config.is_none() {
+        return Err("--embed-config needs --config".to_owned());
+    }
+    if let Some(process) = process {
+        parsed.process = process;
+    }
+    parsed.verbosity = verbosity.unwrap_or(Verbosity::Normal);
+    Ok(parsed)
+}
+
+/// Whether process `name` matches `pattern`, ignoring case. `*` matches any
+/// run of characters and `?` any one.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    // Where to resume after the last `*`: its position in the pattern, and
+    // how much of the name it has swallowed so far.
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(&'*') => {
+                star = Some((p, n));
+                p += 1;
+            },
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            },
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                },
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &[&str]) -> Result<Args, String> {
+        parse(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn no_arguments_injects_into_aworld() {
+        assert_eq!(parse_str(&[]).unwrap(), Args::default());
+    }
+
+    #[test]
+    fn options_take_values_either_way() {
+        let args = parse_str(&["--pid", "1234", "-d", "C:\\aw_vr\\aw_vr.dll", "--config=vr.toml", "-v"]).unwrap();
+        assert_eq!(args.pid, Some(1234));
+        assert_eq!(args.dll, Some(PathBuf::from("C:\\aw_vr\\aw_vr.dll")));
+        assert_eq!(args.config, Some(PathBuf::from("vr.toml")));
+        assert_eq!(args.verbosity, Verbosity::Verbose);
+        assert_eq!(parse_str(&["--eject", "-p", "1234"]).unwrap(), Args { mode: Mode::Eject, pid: Some(1234), ..Args::default() });
+        let args = parse_str(&["-c", "vr.toml", "-e", "--log-dir=logs", "--openxr"]).unwrap();
+        assert!(args.embed_config && args.openxr && !args.log_debugger);
+        assert_eq!(args.log_dir, Some(PathBuf::from("logs")));
+        assert_eq!(parse_str(&["-l", "-n", "aw*.exe"]).unwrap(), Args { mode: Mode::List, process: "aw*.exe".to_owned(), ..Args::default() });
+    }
+
+    #[test]
+    fn launch_passes_on_what_follows_the_dashes() {
+        let args = parse_str(&["--launch", "C:\\AW\\aworld.exe", "-q", "--", "--pid", "world name"]).unwrap();
+        assert_eq!(args.mode, Mode::Launch);
+        assert_eq!(args.launch, Some(PathBuf::from("C:\\AW\\aworld.exe")));
+        assert_eq!(args.launch_args, vec!["--pid".to_owned(), "world name".to_owned()]);
+        assert_eq!(args.pid, None);
+        assert_eq!(args.verbosity, Verbosity::Quiet);
+    }
+
+    #[test]
+    fn bad_command_lines_are_errors() {
+        let bad: &[&[&str]] = &[
+            &["--pid"],
+            &["--pid", "aworld"],
+            &["--frobnicate"],
+            &["-v", "-q"],
+            &["--list", "--pid", "1"],
+            &["--launch", "aworld.exe", "--pid", "1"],
+            &["--process", "aw*", "-L", "aworld.exe"],
+            &["--", "/world"],
+            &["--embed-config"],
+            &["--eject", "--launch", "aworld.exe"],
+            &["--list", "-L", "aworld.exe"],
+            &["--eject", "--config", "vr.toml"],
+        ];
+        for args in bad {
+            assert!(parse_str(args).is_err(), "{:?} parsed", args);
+        }
+    }
+
+    #[test]
+    fn help_wins_over_everything_else() {
+        assert_eq!(parse_str(&["-l", "--help", "--frobnicate"]).unwrap().mode, Mode::Help);
+    }
+
+    #[test]
+    fn patterns_match_names_with_wildcards() {
+        assert!(matches_pattern("aworld.exe", "AWorld.exe"));
+        assert!(matches_pattern("aw*.exe", "aworld.exe"));
+        assert!(matches_pattern("*", "aworld.exe"));
+        assert!(matches_pattern("aworld.e?e", "aworld.exe"));
+        assert!(matches_pattern("*o*d*", "aworld.exe"));
+        assert!(!matches_pattern("aworld", "aworld.exe"));
+        assert!(!matches_pattern("aw?.exe", "aworld.exe"));
+        assert!(!matches_pattern("*.dll", "aworld.exe"));
+    }
+}
@@ -0,0 +1,46 @@
+//! Asking aw_vr.dll to take itself out of a process.
+
+/// Sets the event aw_vr.dll waits on in process `pid`. `Ok(false)` if there's
+/// no such event, because the DLL isn't in the process.
+#[cfg(windows)]
+pub fn request(pid: usize) -> Result<bool, String> {
+    use std::ffi::OsStr;
+    use std::io;
+    use std::os::raw::c_void;
+    use std::os::windows::ffi::OsStrExt;
+
+    use aw_vr_payload;
+
+    const EVENT_MODIFY_STATE: u32 = 0x2;
+    const ERROR_FILE_NOT_FOUND: i32 = 2;
+
+    extern "system" {
+        fn OpenEventW(desired_access: u32, inherit_handle: i32, name: *const u16) -> *mut c_void;
+        fn SetEvent(event: *mut c_void) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    let name: Vec<u16> = OsStr::new(&aw_vr_payload::eject_event_name(pid as u32)).encode_wide().chain(Some(0)).collect();
+    unsafe {
+        let event = OpenEventW(EVENT_MODIFY_STATE, 0, name.as_ptr());
+        if event.is_null() {
+            let error = io::Error::last_os_error();
+            return match error.raw_os_error() {
+                Some(ERROR_FILE_NOT_FOUND) => Ok(false),
+                _ => Err(error.to_string()),
+            };
+        }
+        let set = SetEvent(event);
+        let error = io::Error::last_os_error();
+        CloseHandle(event);
+        if set == 0 {
+            return Err(error.to_string());
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(not(windows))]
+pub fn request(_pid: usize) -> Result<bool, String> {
+    Err("ejecting needs Windows".to_owned())
+}
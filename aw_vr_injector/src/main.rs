@@ -1,19 +1,233 @@
+extern crate aw_vr_payload;
 extern crate easyhook;
 extern crate sysinfo;
 
-use easyhook::rh_inject_library;
+mod args;
+mod eject;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use aw_vr_payload::{feature, ConfigSource, InjectionPayload};
+use easyhook::{create_and_inject, rh_inject_library};
 
 use sysinfo::{ProcessExt, SystemExt};
 
+use args::{Args, Mode, Verbosity};
+
+/// Exit codes, as listed in `args::USAGE`.
+mod exit {
+    pub const INJECTION_FAILED: i32 = 1;
+    pub const USAGE: i32 = 2;
+    pub const NO_PROCESS: i32 = 3;
+    pub const AMBIGUOUS: i32 = 4;
+    /// The DLL, config or executable doesn't exist, or the config can't be read.
+    pub const MISSING_FILE: i32 = 5;
+    pub const NOT_INJECTED: i32 = 6;
+}
+
+const DLL_NAME: &str = "aw_vr.dll";
+
+/// A process that could be injected into.
+struct Candidate {
+    pid: usize,
+    name: String,
+    exe: String,
+}
+
 fn main() {
+    let args = match args::parse(env::args_os().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, args::USAGE);
+            process::exit(exit::USAGE);
+        },
+    };
+    if let Err(code) = run(&args) {
+        process::exit(code);
+    }
+}
+
+fn run(args: &Args) -> Result<(), i32> {
+    let verbose = args.verbosity == Verbosity::Verbose;
+    let normal = args.verbosity >= Verbosity::Normal;
+    match args.mode {
+        Mode::Help => {
+            println!("{}", args::USAGE);
+            return Ok(());
+        },
+        Mode::Version => {
+            println!("aw_vr_injector {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        },
+        Mode::Launch => return launch(args),
+        Mode::List | Mode::Inject | Mode::Eject => {},
+    }
+
     let mut system = sysinfo::System::new();
     system.refresh_processes();
-    let aw = system.get_process_list().values().filter(|process| process.name().to_lowercase() == "aworld.exe").next();
-    if let Some(awproc) = aw {
-        println!("Found AW process id: {}", awproc.pid());
-        rh_inject_library(awproc.pid(), "aw_vr.dll");
-        println!("Error?: {:?}", easyhook::error_string());
-    } else {
-        println!("Unable to find AW!");
+    let candidates: Vec<Candidate> = system.get_process_list().values()
+        .filter(|process| match args.pid {
+            Some(pid) => process.pid() as usize == pid,
+            None => args::matches_pattern(&args.process, process.name()),
+        })
+        .map(|process| Candidate { pid: process.pid() as usize, name: process.name().to_owned(), exe: process.exe().to_owned() })
+        .collect();
+
+    if args.mode == Mode::List {
+        for candidate in &candidates {
+            if verbose {
+                println!("{}\t{}\t{}", candidate.pid, candidate.name, candidate.exe);
+            } else {
+                println!("{}\t{}", candidate.pid, candidate.name);
+            }
+        }
+        if candidates.is_empty() {
+            if normal {
+                eprintln!("No process matches {}", args.process);
+            }
+            return Err(exit::NO_PROCESS);
+        }
+        return Ok(());
+    }
+
+    let target = match (candidates.len(), args.pid) {
+        (1, _) => &candidates[0],
+        (0, Some(pid)) => {
+            eprintln!("No process has id {}", pid);
+            return Err(exit::NO_PROCESS);
+        },
+        (0, None) => {
+            eprintln!("Unable to find a process matching {}. Is Active Worlds running?", args.process);
+            return Err(exit::NO_PROCESS);
+        },
+        _ => {
+            eprintln!("More than one process matches {}; pick one with --pid:", args.process);
+            for candidate in &candidates {
+                eprintln!("{}\t{}", candidate.pid, candidate.name);
+            }
+            return Err(exit::AMBIGUOUS);
+        },
+    };
+
+    if verbose {
+        println!("Found {} (pid {}) at {}", target.name, target.pid, target.exe);
+    }
+    if args.mode == Mode::Eject {
+        return eject(target, normal);
+    }
+    let (dll, payload) = dll_and_payload(args, false)?;
+
+    match rh_inject_library(target.pid, &dll, &payload.encode()) {
+        Ok(()) => {
+            if normal {
+                println!("Injected {} into {} (pid {})", dll.display(), target.name, target.pid);
+            }
+            Ok(())
+        },
+        Err(error) => {
+            eprintln!("Injecting into {} (pid {}) failed: {}", target.name, target.pid, error);
+            Err(exit::INJECTION_FAILED)
+        },
+    }
+}
+
+/// Asks aw_vr.dll in `target` to turn VR off and unload. It does so once AW
+/// next renders; its log says how that went.
+fn eject(target: &Candidate, normal: bool) -> Result<(), i32> {
+    match eject::request(target.pid) {
+        Ok(true) => {
+            if normal {
+                println!("Asked AW_VR to eject from {} (pid {})", target.name, target.pid);
+            }
+            Ok(())
+        },
+        Ok(false) => {
+            eprintln!("AW_VR isn't in {} (pid {})", target.name, target.pid);
+            Err(exit::NOT_INJECTED)
+        },
+        Err(error) => {
+            eprintln!("Ejecting from {} (pid {}) failed: {}", target.name, target.pid, error);
+            Err(exit::INJECTION_FAILED)
+        },
+    }
+}
+
+/// Starts AW suspended with aw_vr.dll injected. The DLL wakes AW once its
+/// hooks are in, so they're there from the first frame.
+fn launch(args: &Args) -> Result<(), i32> {
+    let exe = match args.launch {
+        Some(ref exe) => absolute(exe, "executable")?,
+        None => return Err(exit::USAGE),
+    };
+    let (dll, payload) = dll_and_payload(args, true)?;
+    if args.verbosity == Verbosity::Verbose {
+        println!("Launching {} {:?}", exe.display(), args.launch_args);
+    }
+    match create_and_inject(&exe, &args.launch_args, &dll, &payload.encode()) {
+        Ok(pid) => {
+            if args.verbosity >= Verbosity::Normal {
+                println!("Launched {} (pid {}) with {}", exe.display(), pid, dll.display());
+            }
+            Ok(())
+        },
+        Err(error) => {
+            eprintln!("Launching {} failed: {}", exe.display(), error);
+            Err(exit::INJECTION_FAILED)
+        },
+    }
+}
+
+/// The DLL to inject and what to tell it. AW has its own working directory,
+/// so paths are made absolute.
+fn dll_and_payload(args: &Args, launched: bool) -> Result<(PathBuf, InjectionPayload), i32> {
+    let dll = absolute(&args.dll.clone().unwrap_or_else(default_dll), "DLL")?;
+    let mut payload = InjectionPayload::new(env!("CARGO_PKG_VERSION"));
+    if let Some(ref config) = args.config {
+        let config = absolute(config, "config")?;
+        payload.config = if args.embed_config {
+            ConfigSource::Inline(fs::read_to_string(&config).map_err(|error| {
+                eprintln!("Unable to read config {}: {}", config.display(), error);
+                exit::MISSING_FILE
+            })?)
+        } else {
+            ConfigSource::Path(config)
+        };
+    }
+    if let Some(ref log_dir) = args.log_dir {
+        // The DLL creates the directory if it has to, so it needn't exist yet.
+        payload.log_dir = Some(env::current_dir().map(|dir| dir.join(log_dir)).unwrap_or_else(|_| log_dir.clone()));
+    }
+    let flags = [(launched, feature::LAUNCHED), (args.openxr, feature::OPENXR), (args.log_debugger, feature::LOG_DEBUGGER)];
+    for &(enabled, flag) in &flags {
+        if enabled {
+            payload.features |= flag;
+        }
+    }
+    if args.verbosity == Verbosity::Verbose {
+        println!("DLL: {}", dll.display());
+        println!("Payload: {:?}", payload);
+    }
+    Ok((dll, payload))
+}
+
+/// aw_vr.dll next to the injector, or in the working directory if it isn't.
+fn default_dll() -> PathBuf {
+    env::current_exe().ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(DLL_NAME)))
+        .filter(|dll| dll.is_file())
+        .unwrap_or_else(|| PathBuf::from(DLL_NAME))
+}
+
+/// `path` from the working directory, if it's a file. Not `fs::canonicalize`,
+/// whose `\\?\` paths would reach EasyHook, the DLL and the output.
+fn absolute(path: &Path, what: &str) -> Result<PathBuf, i32> {
+    let path = env::current_dir().map(|dir| dir.join(path)).unwrap_or_else(|_| path.to_owned());
+    if !path.is_file() {
+        eprintln!("Unable to find {} {}", what, path.display());
+        return Err(exit::MISSING_FILE);
     }
+    Ok(path)
 }
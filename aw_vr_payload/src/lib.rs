@@ -0,0 +1,270 @@
+//! What the injector tells aw_vr.dll, through EasyHook's pass-through buffer,
+//! and how it later asks it to leave.
+//!
+//! The payload is a little-endian byte string: the magic `AWVR`, a `u16`
+//! format version, then the fields in the order they were added. Strings are a
+//! `u32` byte length followed by UTF-8, and optional values are preceded by a
+//! `u8` tag. Fields are only ever appended, with a version bump, so a DLL
+//! reads a payload of any version: it reads the fields it knows and leaves any
+//! newer ones unread.
+
+use std::fmt;
+use std::path::PathBuf;
+
+const MAGIC: &[u8; 4] = b"AWVR";
+
+/// The format version `encode` writes.
+pub const VERSION: u16 = 1;
+
+/// Feature flags, set by the injector to change what the DLL does.
+pub mod feature {
+    /// The injector started AW suspended, and the DLL has to wake it.
+    pub const LAUNCHED: u32 = 1 << 0;
+    /// Use the OpenXR backend, as if `AW_VR_BACKEND=openxr` were set.
+    pub const OPENXR: u32 = 1 << 1;
+    /// Also send the log to the debugger.
+    pub const LOG_DEBUGGER: u32 = 1 << 2;
+}
+
+/// The named event aw_vr.dll waits on in process `pid`. Setting it ejects the
+/// DLL: VR is turned off, the hooks removed and the DLL unloaded.
+pub fn eject_event_name(pid: u32) -> String {
+    format!("Local\\aw_vr_eject_{}", pid)
+}
+
+/// Where the DLL gets its settings.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigSource {
+    /// Whatever `aw_vr.toml` sits next to the DLL.
+    Default,
+    /// A file, watched for changes.
+    Path(PathBuf),
+    /// The contents of a file, read by the injector.
+    Inline(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InjectionPayload {
+    /// The version of the injector that sent this, for the log.
+    pub injector_version: String,
+    pub config: ConfigSource,
+    /// Where to log, instead of `AW_VR_LOG_DIR` or AW's directory.
+    pub log_dir: Option<PathBuf>,
+    /// `feature` bits. Bits the DLL doesn't know are ignored.
+    pub features: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PayloadError {
+    /// The data doesn't start with `AWVR`.
+    BadMagic,
+    /// The data ends in the middle of a field.
+    Truncated,
+    /// A string isn't UTF-8.
+    BadString,
+    /// An optional value has a tag that isn't defined.
+    BadTag(u8),
+}
+
+impl fmt::Display for PayloadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PayloadError::BadMagic => write!(f, "not an AW_VR injection payload"),
+            PayloadError::Truncated => write!(f, "payload is truncated"),
+            PayloadError::BadString => write!(f, "payload has a string that isn't UTF-8"),
+            PayloadError::BadTag(tag) => write!(f, "payload has an unknown tag {}", tag),
+        }
+    }
+}
+
+impl InjectionPayload {
+    /// A payload from this injector, with the defaults for everything else.
+    pub fn new(injector_version: &str) -> InjectionPayload {
+        InjectionPayload {
+            injector_version: injector_version.to_owned(),
+            config: ConfigSource::Default,
+            log_dir: None,
+            features: 0,
+        }
+    }
+
+    pub fn has_feature(&self, feature: u32) -> bool {
+        self.features & feature == feature
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer(Vec::new());
+        writer.0.extend_from_slice(MAGIC);
+        writer.u16(VERSION);
+        writer.string(&self.injector_version);
+        match self.config {
+            ConfigSource::Default => writer.u8(0),
+            ConfigSource::Path(ref path) => {
+                writer.u8(1);
+                writer.string(&path.to_string_lossy());
+            },
+            ConfigSource::Inline(ref text) => {
+                writer.u8(2);
+                writer.string(text);
+            },
+        }
+        match self.log_dir {
+            None => writer.u8(0),
+            Some(ref dir) => {
+                writer.u8(1);
+                writer.string(&dir.to_string_lossy());
+            },
+        }
+        writer.u32(self.features);
+        writer.0
+    }
+
+    /// Reads a payload. Anything after the fields this version knows about is
+    /// left unread.
+    pub fn decode(data: &[u8]) -> Result<InjectionPayload, PayloadError> {
+        if data.len() < MAGIC.len() || &data[..MAGIC.len()] != MAGIC {
+            return Err(PayloadError::BadMagic);
+        }
+        let mut reader = Reader(&data[MAGIC.len()..]);
+        // Every version so far starts with version 1's fields.
+        let _version = reader.u16()?;
+        let injector_version = reader.string()?;
+        let config = match reader.u8()? {
+            0 => ConfigSource::Default,
+            1 => ConfigSource::Path(PathBuf::from(reader.string()?)),
+            2 => ConfigSource::Inline(reader.string()?),
+            tag => return Err(PayloadError::BadTag(tag)),
+        };
+        let log_dir = match reader.u8()? {
+            0 => None,
+            1 => Some(PathBuf::from(reader.string()?)),
+            tag => return Err(PayloadError::BadTag(tag)),
+        };
+        let features = reader.u32()?;
+        Ok(InjectionPayload { injector_version, config, log_dir, features })
+    }
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], PayloadError> {
+        if self.0.len() < length {
+            return Err(PayloadError::Truncated);
+        }
+        let (taken, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, PayloadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, PayloadError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, PayloadError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn string(&mut self) -> Result<String, PayloadError> {
+        let length = self.u32()? as usize;
+        String::from_utf8(self.take(length)?.to_vec()).map_err(|_| PayloadError::BadString)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(payload: &InjectionPayload) {
+        assert_eq!(&InjectionPayload::decode(&payload.encode()).unwrap(), payload);
+    }
+
+    #[test]
+    fn payloads_survive_a_round_trip() {
+        round_trip(&InjectionPayload::new("0.1.0"));
+        round_trip(&InjectionPayload {
+            injector_version: "0.1.0".to_owned(),
+            config: ConfigSource::Path(PathBuf::from("C:\\Users\\Ünïcode\\aw_vr.toml")),
+            log_dir: Some(PathBuf::from("C:\\logs")),
+            features: feature::LAUNCHED | feature::LOG_DEBUGGER,
+        });
+        round_trip(&InjectionPayload {
+            injector_version: String::new(),
+            config: ConfigSource::Inline("world_scale = 5.0\n[keys]\nrun = \"shift\"\n".to_owned()),
+            log_dir: None,
+            features: feature::OPENXR,
+        });
+    }
+
+    #[test]
+    fn the_format_is_stable() {
+        let payload = InjectionPayload {
+            injector_version: "1.2".to_owned(),
+            config: ConfigSource::Path(PathBuf::from("a")),
+            log_dir: None,
+            features: feature::OPENXR,
+        };
+        assert_eq!(payload.encode(), b"AWVR\x01\x00\x03\x00\x00\x001.2\x01\x01\x00\x00\x00a\x00\x02\x00\x00\x00".to_vec());
+    }
+
+    #[test]
+    fn features_are_checked_by_bit() {
+        let payload = InjectionPayload { features: feature::LAUNCHED | 1 << 31, ..InjectionPayload::new("0.1.0") };
+        assert!(payload.has_feature(feature::LAUNCHED));
+        assert!(!payload.has_feature(feature::OPENXR));
+    }
+
+    #[test]
+    fn damaged_payloads_are_rejected() {
+        let encoded = InjectionPayload::new("0.1.0").encode();
+        assert_eq!(InjectionPayload::decode(b""), Err(PayloadError::BadMagic));
+        assert_eq!(InjectionPayload::decode(b"C:\\aw_vr.toml"), Err(PayloadError::BadMagic));
+        assert_eq!(InjectionPayload::decode(&encoded[..encoded.len() - 1]), Err(PayloadError::Truncated));
+
+        let mut bad_tag = encoded.clone();
+        let tag = 4 + 2 + 4 + "0.1.0".len();
+        bad_tag[tag] = 7;
+        assert_eq!(InjectionPayload::decode(&bad_tag), Err(PayloadError::BadTag(7)));
+    }
+
+    #[test]
+    fn eject_events_are_per_process() {
+        assert_eq!(eject_event_name(1234), "Local\\aw_vr_eject_1234");
+    }
+
+    #[test]
+    fn newer_versions_are_read_as_far_as_known() {
+        let payload = InjectionPayload::new("0.1.0");
+        let mut encoded = payload.encode();
+        encoded[4] = 2;
+        encoded.extend_from_slice(b"from the future");
+        assert_eq!(InjectionPayload::decode(&encoded).unwrap(), payload);
+    }
+}
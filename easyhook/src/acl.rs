@@ -0,0 +1,101 @@
+//! Access control lists, which pick the threads hooks intercept.
+//!
+//! A thread is intercepted by a hook only if both the global ACL and the
+//! hook's own let it through. The global ACL starts out letting every thread
+//! through, and a hook's starts out letting none.
+
+use super::check;
+use error::EasyHookError;
+use sys::{self, HOOK_TRACE_INFO};
+
+/// Stands for the calling thread in an ACL, or in `Hook::is_thread_intercepted`.
+pub const CURRENT_THREAD: u32 = 0;
+
+/// The most threads EasyHook takes in one ACL.
+const MAX_THREADS: usize = 128;
+
+/// Which threads are intercepted, by thread id.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Acl {
+    /// Only these threads.
+    Inclusive(Vec<u32>),
+    /// Every thread but these.
+    Exclusive(Vec<u32>),
+}
+
+impl Acl {
+    /// Every thread.
+    pub fn all() -> Acl {
+        Acl::Exclusive(Vec::new())
+    }
+
+    /// No thread at all.
+    pub fn none() -> Acl {
+        Acl::Inclusive(Vec::new())
+    }
+
+    /// The threads as EasyHook takes them, checking there aren't too many
+    /// for `call`.
+    fn threads(&self, call: &'static str) -> Result<Vec<usize>, EasyHookError> {
+        let threads = match *self {
+            Acl::Inclusive(ref threads) | Acl::Exclusive(ref threads) => threads,
+        };
+        if threads.len() > MAX_THREADS {
+            return Err(EasyHookError::invalid_argument(call, 2, format!("{} threads is more than the {} an ACL holds", threads.len(), MAX_THREADS)));
+        }
+        let mut list: Vec<usize> = threads.iter().map(|&thread| thread as usize).collect();
+        // EasyHook wants a real pointer even for no threads.
+        list.reserve(1);
+        Ok(list)
+    }
+
+    /// Sets `hook`'s ACL to this one.
+    pub(crate) unsafe fn apply(&self, hook: *mut HOOK_TRACE_INFO) -> Result<(), EasyHookError> {
+        match *self {
+            Acl::Inclusive(_) => {
+                let mut threads = self.threads("LhSetInclusiveACL")?;
+                check("LhSetInclusiveACL", sys::LhSetInclusiveACL(threads.as_mut_ptr(), threads.len(), hook))
+            },
+            Acl::Exclusive(_) => {
+                let mut threads = self.threads("LhSetExclusiveACL")?;
+                check("LhSetExclusiveACL", sys::LhSetExclusiveACL(threads.as_mut_ptr(), threads.len(), hook))
+            },
+        }
+    }
+}
+
+/// Sets the ACL every hook's own is combined with.
+pub fn set_global_acl(acl: &Acl) -> Result<(), EasyHookError> {
+    unsafe {
+        match *acl {
+            Acl::Inclusive(_) => {
+                let mut threads = acl.threads("LhSetGlobalInclusiveACL")?;
+                check("LhSetGlobalInclusiveACL", sys::LhSetGlobalInclusiveACL(threads.as_mut_ptr(), threads.len()))
+            },
+            Acl::Exclusive(_) => {
+                let mut threads = acl.threads("LhSetGlobalExclusiveACL")?;
+                check("LhSetGlobalExclusiveACL", sys::LhSetGlobalExclusiveACL(threads.as_mut_ptr(), threads.len()))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::NtStatus;
+
+    #[test]
+    fn threads_are_passed_as_given() {
+        assert_eq!(Acl::all().threads("LhSetExclusiveACL").unwrap(), Vec::<usize>::new());
+        assert_eq!(Acl::Inclusive(vec![CURRENT_THREAD, 1234]).threads("LhSetInclusiveACL").unwrap(), vec![0, 1234]);
+    }
+
+    #[test]
+    fn too_many_threads_are_an_invalid_argument() {
+        let error = Acl::Exclusive((1..=129).collect()).threads("LhSetGlobalExclusiveACL").unwrap_err();
+        assert_eq!(error.call, "LhSetGlobalExclusiveACL");
+        assert_eq!(error.status, NtStatus::InvalidArgument(2));
+        assert!(Acl::Inclusive((1..=128).collect()).threads("LhSetInclusiveACL").is_ok());
+    }
+}
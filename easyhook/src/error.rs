@@ -0,0 +1,162 @@
+//! EasyHook's NTSTATUS results as Rust errors.
+
+use std::error::Error;
+use std::fmt;
+
+/// The NTSTATUS codes EasyHook fails with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NtStatus {
+    InvalidParameter,
+    /// One of the arguments, counted from 1, was invalid.
+    InvalidArgument(u8),
+    NotSupported,
+    AccessDenied,
+    NoMemory,
+    InsufficientResources,
+    NotFound,
+    BufferTooSmall,
+    InternalError,
+    Timeout,
+    /// The target is 64-bit and this is 32-bit, or the other way around.
+    WowAssertion,
+    Other(u32),
+}
+
+impl NtStatus {
+    pub fn from_code(code: u32) -> NtStatus {
+        match code {
+            0xC000_000D => NtStatus::InvalidParameter,
+            0xC000_00EF..=0xC000_00F8 => NtStatus::InvalidArgument((code - 0xC000_00EF + 1) as u8),
+            0xC000_00BB => NtStatus::NotSupported,
+            0xC000_0022 => NtStatus::AccessDenied,
+            0xC000_0017 => NtStatus::NoMemory,
+            0xC000_009A => NtStatus::InsufficientResources,
+            0xC000_0225 => NtStatus::NotFound,
+            0xC000_0023 => NtStatus::BufferTooSmall,
+            0xC000_00E5 => NtStatus::InternalError,
+            0x0000_0102 => NtStatus::Timeout,
+            0xC000_9898 => NtStatus::WowAssertion,
+            code => NtStatus::Other(code),
+        }
+    }
+
+    pub fn code(self) -> u32 {
+        match self {
+            NtStatus::InvalidParameter => 0xC000_000D,
+            NtStatus::InvalidArgument(argument) => 0xC000_00EF + u32::from(argument) - 1,
+            NtStatus::NotSupported => 0xC000_00BB,
+            NtStatus::AccessDenied => 0xC000_0022,
+            NtStatus::NoMemory => 0xC000_0017,
+            NtStatus::InsufficientResources => 0xC000_009A,
+            NtStatus::NotFound => 0xC000_0225,
+            NtStatus::BufferTooSmall => 0xC000_0023,
+            NtStatus::InternalError => 0xC000_00E5,
+            NtStatus::Timeout => 0x0000_0102,
+            NtStatus::WowAssertion => 0xC000_9898,
+            NtStatus::Other(code) => code,
+        }
+    }
+}
+
+impl fmt::Display for NtStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NtStatus::InvalidParameter => write!(f, "invalid parameter"),
+            NtStatus::InvalidArgument(argument) => write!(f, "invalid argument {}", argument),
+            NtStatus::NotSupported => write!(f, "not supported"),
+            NtStatus::AccessDenied => write!(f, "access denied"),
+            NtStatus::NoMemory => write!(f, "out of memory"),
+            NtStatus::InsufficientResources => write!(f, "insufficient resources"),
+            NtStatus::NotFound => write!(f, "not found"),
+            NtStatus::BufferTooSmall => write!(f, "buffer too small"),
+            NtStatus::InternalError => write!(f, "internal error"),
+            NtStatus::Timeout => write!(f, "timed out"),
+            NtStatus::WowAssertion => write!(f, "32-bit and 64-bit mismatch"),
+            NtStatus::Other(_) => write!(f, "failed"),
+        }
+    }
+}
+
+/// An EasyHook call that failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EasyHookError {
+    /// The EasyHook function that failed.
+    pub call: &'static str,
+    pub status: NtStatus,
+    /// What `RtlGetLastErrorString` said about it, if anything.
+    pub message: Option<String>,
+}
+
+impl EasyHookError {
+    /// The error for `call` failing with `code`, along with EasyHook's
+    /// explanation of the last error.
+    pub fn from_code(call: &'static str, code: u32) -> EasyHookError {
+        EasyHookError {
+            call,
+            status: NtStatus::from_code(code),
+            message: super::error_string(),
+        }
+    }
+
+    /// `call` couldn't be made because an argument was invalid before EasyHook
+    /// even saw it.
+    pub fn invalid_argument(call: &'static str, argument: u8, message: String) -> EasyHookError {
+        EasyHookError {
+            call,
+            status: NtStatus::InvalidArgument(argument),
+            message: Some(message),
+        }
+    }
+
+    /// `call` couldn't find what it was asked for.
+    pub fn not_found(call: &'static str, message: String) -> EasyHookError {
+        EasyHookError {
+            call,
+            status: NtStatus::NotFound,
+            message: Some(message),
+        }
+    }
+
+    pub fn code(&self) -> u32 {
+        self.status.code()
+    }
+}
+
+impl fmt::Display for EasyHookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} (NTSTATUS 0x{:08X})", self.call, self.status, self.code())?;
+        match self.message {
+            Some(ref message) if !message.is_empty() => write!(f, ": {}", message),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Error for EasyHookError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_codes_round_trip() {
+        for &code in &[0xC000_000D, 0xC000_00EF, 0xC000_00F2, 0xC000_00F8, 0xC000_0022, 0xC000_9898, 0x0000_0102, 0xDEAD_BEEF] {
+            assert_eq!(NtStatus::from_code(code).code(), code);
+        }
+        assert_eq!(NtStatus::from_code(0xC000_00F1), NtStatus::InvalidArgument(3));
+        assert_eq!(NtStatus::from_code(0xC000_00BB), NtStatus::NotSupported);
+        assert_eq!(NtStatus::from_code(0xC000_0001), NtStatus::Other(0xC000_0001));
+    }
+
+    #[test]
+    fn errors_say_what_failed() {
+        let error = EasyHookError {
+            call: "RhInjectLibrary",
+            status: NtStatus::AccessDenied,
+            message: Some("Unable to open target process.".to_owned()),
+        };
+        assert_eq!(error.to_string(), "RhInjectLibrary: access denied (NTSTATUS 0xC0000022): Unable to open target process.");
+        let error = EasyHookError { message: None, status: NtStatus::Other(0xC000_0001), ..error };
+        assert_eq!(error.to_string(), "RhInjectLibrary: failed (NTSTATUS 0xC0000001)");
+    }
+}
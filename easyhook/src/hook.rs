@@ -0,0 +1,164 @@
+//! Installed hooks, as handles that remove them again.
+
+use std::os::raw::c_void;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::check;
+use acl::Acl;
+use error::EasyHookError;
+use sys::{self, HOOK_TRACE_INFO};
+
+/// A hook EasyHook has installed. Dropping it uninstalls the hook; EasyHook
+/// frees the trampoline once no thread is inside it any more, which
+/// `HookRegistry::remove_all` waits for.
+pub struct Hook {
+    /// Boxed because EasyHook keeps the address until the hook is uninstalled.
+    trace_info: Box<HOOK_TRACE_INFO>,
+    installed: bool,
+    /// Where a `StaticHook` keeps this hook's bypass address, to be cleared
+    /// when it's uninstalled.
+    bypass: Option<&'static AtomicUsize>,
+}
+
+// EasyHook's hook functions may be called from any thread.
+unsafe impl Send for Hook {}
+
+impl Hook {
+    /// Hooks `entry` with `hook`, intercepting every thread.
+    ///
+    /// # Safety
+    ///
+    /// `entry` must point to a function and `hook` to one with the same
+    /// signature and calling convention.
+    pub unsafe fn install(entry: *mut c_void, hook: *mut c_void) -> Result<Hook, EasyHookError> {
+        let mut trace_info = Box::new(HOOK_TRACE_INFO::new());
+        check("LhInstallHook", sys::LhInstallHook(entry, hook, ptr::null_mut(), &mut *trace_info))?;
+        let mut hook = Hook { trace_info, installed: true, bypass: None };
+        // EasyHook installs hooks disabled for every thread.
+        hook.enable()?;
+        Ok(hook)
+    }
+
+    /// Intercepts every thread.
+    pub fn enable(&mut self) -> Result<(), EasyHookError> {
+        self.set_acl(&Acl::all())
+    }
+
+    /// Lets every thread through to the original function.
+    pub fn disable(&mut self) -> Result<(), EasyHookError> {
+        self.set_acl(&Acl::none())
+    }
+
+    /// Intercepts only the threads `acl` picks, and that the global ACL
+    /// lets through.
+    pub fn set_acl(&mut self, acl: &Acl) -> Result<(), EasyHookError> {
+        unsafe {
+            acl.apply(&mut *self.trace_info)
+        }
+    }
+
+    /// Whether `thread` would be intercepted, counting both ACLs.
+    pub fn is_thread_intercepted(&mut self, thread: u32) -> Result<bool, EasyHookError> {
+        let mut intercepted = 0;
+        unsafe {
+            check("LhIsThreadIntercepted", sys::LhIsThreadIntercepted(&mut *self.trace_info, thread as usize, &mut intercepted))?;
+        }
+        Ok(intercepted != 0)
+    }
+
+    /// An address that calls the hooked function as if it weren't, for as
+    /// long as the hook is installed.
+    pub fn bypass_address(&mut self) -> Result<*mut c_void, EasyHookError> {
+        let mut address = ptr::null_mut();
+        unsafe {
+            check("LhGetHookBypassAddress", sys::LhGetHookBypassAddress(&mut *self.trace_info, &mut address))?;
+        }
+        Ok(address)
+    }
+
+    /// Has `bypass` cleared when the hook is uninstalled.
+    pub(crate) fn clear_on_uninstall(&mut self, bypass: &'static AtomicUsize) {
+        self.bypass = Some(bypass);
+    }
+
+    /// Removes the hook now, rather than when it's dropped, to see whether
+    /// that worked.
+    pub fn uninstall(mut self) -> Result<(), EasyHookError> {
+        self.installed = false;
+        if let Some(bypass) = self.bypass {
+            bypass.store(0, Ordering::SeqCst);
+        }
+        unsafe {
+            check("LhUninstallHook", sys::LhUninstallHook(&mut *self.trace_info))
+        }
+    }
+}
+
+impl Drop for Hook {
+    fn drop(&mut self) {
+        if self.installed {
+            if let Some(bypass) = self.bypass {
+                bypass.store(0, Ordering::SeqCst);
+            }
+            unsafe {
+                sys::LhUninstallHook(&mut *self.trace_info);
+            }
+        }
+    }
+}
+
+/// The hooks a library has installed, by name, so they can all be removed
+/// before it's unloaded.
+#[derive(Default)]
+pub struct HookRegistry {
+    hooks: Vec<(&'static str, Hook)>,
+}
+
+impl HookRegistry {
+    pub fn new() -> HookRegistry {
+        HookRegistry::default()
+    }
+
+    /// Keeps `hook` under `name`, uninstalling any hook already there.
+    pub fn add(&mut self, name: &'static str, hook: Hook) {
+        self.hooks.retain(|&(other, _)| other != name);
+        self.hooks.push((name, hook));
+    }
+
+    pub fn get(&mut self, name: &str) -> Option<&mut Hook> {
+        self.hooks.iter_mut().find(|&&mut (other, _)| other == name).map(|&mut (_, ref mut hook)| hook)
+    }
+
+    /// Sets every hook's ACL to `acl`, stopping at the first that fails.
+    pub fn set_acl(&mut self, acl: &Acl) -> Result<(), EasyHookError> {
+        self.hooks.iter_mut().try_for_each(|&mut (_, ref mut hook)| hook.set_acl(acl))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.hooks.iter().map(|&(name, _)| name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Uninstalls every hook, then waits until no thread is still running
+    /// in one, after which the hook functions' code may be unloaded. Every
+    /// hook is tried; the first failure is returned.
+    pub fn remove_all(&mut self) -> Result<(), EasyHookError> {
+        let mut result = Ok(());
+        for (_, hook) in self.hooks.drain(..) {
+            let uninstalled = hook.uninstall();
+            if result.is_ok() {
+                result = uninstalled;
+            }
+        }
+        let waited = unsafe { check("LhWaitForPendingRemovals", sys::LhWaitForPendingRemovals()) };
+        result.and(waited)
+    }
+}
@@ -1,35 +1,124 @@
 extern crate widestring;
 
+mod acl;
+mod error;
+mod hook;
 mod sys;
+mod typed;
 
-use std::os::raw::{c_ulong, c_void};
+use std::os::raw::c_void;
 use std::ptr;
 use std::ffi::OsStr;
-use std::mem;
 
 use widestring::WideCString;
 
+pub use acl::{set_global_acl, Acl, CURRENT_THREAD};
+pub use error::{EasyHookError, NtStatus};
+pub use hook::{Hook, HookRegistry};
+pub use sys::{HOOK_TRACE_INFO, REMOTE_ENTRY_INFO};
+pub use typed::{resolve, StaticHook};
 
-pub fn rh_inject_library<S: AsRef<OsStr>>(pid: usize, library: S) {
+/// Turns the NTSTATUS `call` returned into a result.
+fn check(call: &'static str, status: u32) -> Result<(), EasyHookError> {
+    if status == 0 {
+        Ok(())
+    } else {
+        Err(EasyHookError::from_code(call, status))
+    }
+}
+
+/// `string` as a nul-terminated wide string, for `call`'s `argument`th argument.
+fn wide<S: AsRef<OsStr>>(call: &'static str, argument: u8, string: S) -> Result<Vec<u16>, EasyHookError> {
+    match WideCString::from_str(&string) {
+        Ok(wide) => Ok(wide.into_vec_with_nul()),
+        Err(_) => Err(EasyHookError::invalid_argument(call, argument, format!("{:?} contains a nul", string.as_ref()))),
+    }
+}
+
+/// Injects `library` into process `pid`. `pass_through` is copied into the
+/// target, where the library's entry point gets it through `user_data`.
+pub fn rh_inject_library<S: AsRef<OsStr>>(pid: usize, library: S, pass_through: &[u8]) -> Result<(), EasyHookError> {
+    let mut library = wide("RhInjectLibrary", 4, library)?;
+    let mut pass_through = pass_through.to_vec();
+    let pass_through_ptr = if pass_through.is_empty() { ptr::null_mut() } else { pass_through.as_mut_ptr() as *mut c_void };
     unsafe {
-        sys::RhInjectLibrary(pid, 0, 0, WideCString::from_str(library).unwrap().into_raw(), ptr::null_mut(), ptr::null_mut(), 0);
+        check("RhInjectLibrary", sys::RhInjectLibrary(pid, 0, 0, library.as_mut_ptr(), ptr::null_mut(), pass_through_ptr, pass_through.len()))
     }
 }
 
-pub unsafe fn lh_install_hook(entry: *mut c_void, hook: *mut c_void) -> &'static sys::HOOK_TRACE_INFO {
-    let mut hook_trace_info = Box::new(sys::HOOK_TRACE_INFO::new());
-    sys::LhInstallHook(entry, hook, ptr::null_mut(), &mut *hook_trace_info as *mut _);
-    sys::LhSetExclusiveACL((&mut [0]).as_mut_ptr(), 1, &mut *hook_trace_info as *mut _);
-    Box::leak(hook_trace_info)
+/// Starts `exe` suspended with `args`, in the directory `exe` is in, and
+/// injects `library` into it before any of its code runs, returning its
+/// process id. `pass_through` is handed over as with `rh_inject_library`. The
+/// process stays suspended until the library calls `rh_wake_up_process`.
+pub fn create_and_inject<P, A, L>(exe: P, args: &[A], library: L, pass_through: &[u8]) -> Result<usize, EasyHookError>
+    where P: AsRef<OsStr>, A: AsRef<str>, L: AsRef<OsStr>
+{
+    let mut exe = wide("RhCreateAndInject", 1, exe)?;
+    let mut command_line = wide("RhCreateAndInject", 2, command_line(args))?;
+    let mut library = wide("RhCreateAndInject", 5, library)?;
+    let mut pass_through = pass_through.to_vec();
+    let pass_through_ptr = if pass_through.is_empty() { ptr::null_mut() } else { pass_through.as_mut_ptr() as *mut c_void };
+    let mut pid = 0;
+    unsafe {
+        check("RhCreateAndInject", sys::RhCreateAndInject(exe.as_mut_ptr(), command_line.as_mut_ptr(), 0, 0, library.as_mut_ptr(), ptr::null_mut(), pass_through_ptr, pass_through.len(), &mut pid))?;
+    }
+    Ok(pid)
 }
 
-fn lh_set_global_exclusive_acl(threads: &mut [usize]) {
-    let length = threads.len();
+/// Lets a process started by `create_and_inject` run. Called by the injected
+/// library once it's ready; fails harmlessly in a process that wasn't.
+pub fn rh_wake_up_process() -> Result<(), EasyHookError> {
     unsafe {
-        sys::LhSetGlobalExclusiveACL(threads.as_mut_ptr(), length);
+        check("RhWakeUpProcess", sys::RhWakeUpProcess())
+    }
+}
+
+/// Joins `args` into a Windows command line that splits back into them.
+fn command_line<A: AsRef<str>>(args: &[A]) -> String {
+    let mut line = String::new();
+    for arg in args {
+        let arg = arg.as_ref();
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        if !arg.is_empty() && !arg.contains(&[' ', '\t', '"'][..]) {
+            line.push_str(arg);
+            continue;
+        }
+        // Backslashes are only special before a quote, where they're doubled.
+        // Those already written count once, so as many again are added.
+        line.push('"');
+        let mut backslashes = 0;
+        for c in arg.chars() {
+            match c {
+                '\\' => backslashes += 1,
+                '"' => {
+                    line.extend((0..backslashes + 1).map(|_| '\\'));
+                    backslashes = 0;
+                },
+                _ => backslashes = 0,
+            }
+            line.push(c);
+        }
+        line.extend((0..backslashes).map(|_| '\\'));
+        line.push('"');
+    }
+    line
+}
+
+/// The pass-through data the injector gave `rh_inject_library`.
+///
+/// # Safety
+///
+/// `info` must be null or the pointer EasyHook passed the entry point.
+pub unsafe fn user_data<'a>(info: *const REMOTE_ENTRY_INFO) -> &'a [u8] {
+    match info.as_ref() {
+        Some(info) if !info.UserData.is_null() => std::slice::from_raw_parts(info.UserData, info.UserDataSize as usize),
+        _ => &[],
     }
 }
 
+/// EasyHook's description of the last error, if it has one.
 pub fn error_string() -> Option<String> {
     let err = unsafe {sys::RtlGetLastErrorString()};
     if !err.is_null() {
@@ -37,4 +126,25 @@ pub fn error_string() -> Option<String> {
     } else {
         None
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_with_nuls_are_invalid_arguments() {
+        assert_eq!(wide("RhInjectLibrary", 4, "aw_vr.dll").unwrap().last(), Some(&0));
+        let error = wide("RhInjectLibrary", 4, "aw_vr\0.dll").unwrap_err();
+        assert_eq!(error.status, NtStatus::InvalidArgument(4));
+        assert_eq!(error.code(), 0xC000_00F2);
+    }
+
+    #[test]
+    fn command_lines_quote_only_what_needs_it() {
+        assert_eq!(command_line::<&str>(&[]), "");
+        assert_eq!(command_line(&["/world", "AW"]), "/world AW");
+        assert_eq!(command_line(&["C:\\Program Files\\", ""]), "\"C:\\Program Files\\\\\" \"\"");
+        assert_eq!(command_line(&["say \"hi\"", "a\\\"b"]), "\"say \\\"hi\\\"\" \"a\\\\\\\"b\"");
+    }
+}
@@ -1,25 +1,51 @@
-use std::os::raw::{c_ulong, c_void};
+#[cfg(windows)]
+use std::os::raw::c_char;
+use std::os::raw::c_void;
 use std::ptr;
 
+/// EasyHook's handle to an installed hook.
 #[repr(C)]
 pub struct HOOK_TRACE_INFO {
     link: *mut c_void
 }
 
 impl HOOK_TRACE_INFO {
-    pub fn new() -> Self {
+    pub(crate) fn new() -> Self {
         HOOK_TRACE_INFO {
             link: ptr::null_mut()
         }
     }
 }
 
-#[link(name="easyhook32")]
+/// What EasyHook hands the injected library's entry point.
+#[repr(C)]
+#[allow(non_snake_case)]
+pub struct REMOTE_ENTRY_INFO {
+    pub HostPID: u32,
+    pub UserData: *mut u8,
+    pub UserDataSize: u32,
+}
+
+#[cfg_attr(windows, link(name="easyhook32"))]
 extern "system" {
     pub fn RhInjectLibrary(pid: usize, wakeup_thread: usize, options: usize, library_x86: *mut u16, library_x64: *mut u16, passthrough: *mut c_void, passthrough_size: usize) -> u32;
+    pub fn RhCreateAndInject(exe_path: *mut u16, command_line: *mut u16, process_creation_flags: usize, injection_options: usize, library_x86: *mut u16, library_x64: *mut u16, passthrough: *mut c_void, passthrough_size: usize, process_id: *mut usize) -> u32;
+    pub fn RhWakeUpProcess() -> u32;
     pub fn LhInstallHook(entry: *mut c_void, hook: *mut c_void, callback: *mut c_void, trace_info: *mut HOOK_TRACE_INFO) -> u32;
+    pub fn LhUninstallHook(trace_info: *mut HOOK_TRACE_INFO) -> u32;
+    pub fn LhWaitForPendingRemovals() -> u32;
+    pub fn LhSetInclusiveACL(threads: *mut usize, threads_size: usize, hook: *mut HOOK_TRACE_INFO) -> u32;
     pub fn LhSetExclusiveACL(threads: *mut usize, threads_size: usize, hook: *mut HOOK_TRACE_INFO) -> u32;
+    pub fn LhSetGlobalInclusiveACL(threads: *mut usize, threads_size: usize) -> u32;
     pub fn LhSetGlobalExclusiveACL(threads: *mut usize, threads_size: usize) -> u32;
+    pub fn LhIsThreadIntercepted(hook: *mut HOOK_TRACE_INFO, thread_id: usize, result: *mut i32) -> u32;
+    pub fn LhGetHookBypassAddress(hook: *mut HOOK_TRACE_INFO, address: *mut *mut c_void) -> u32;
     pub fn RtlGetLastErrorString() -> *mut u16;
 }
 
+#[cfg(windows)]
+extern "system" {
+    pub fn LoadLibraryW(name: *const u16) -> *mut c_void;
+    pub fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+}
+
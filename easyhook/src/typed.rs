@@ -0,0 +1,149 @@
+//! Hooks declared with their function's type, which find their own target
+//! and call the original without going through the hook again.
+
+use std::mem;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use error::EasyHookError;
+use hook::Hook;
+
+/// A hook on `symbol` in `module`, replacing it with `detour`. `F` is the
+/// function's pointer type, such as `extern "C" fn(*mut c_void) -> i32`.
+///
+/// Meant to be a `static`, so the detour can reach the original through it;
+/// `static_hooks!` declares them.
+pub struct StaticHook<F> {
+    module: &'static str,
+    symbol: &'static str,
+    detour: F,
+    /// The function itself, once resolved.
+    target: AtomicUsize,
+    /// What calls the function past the hook, while it's installed.
+    bypass: AtomicUsize,
+}
+
+impl<F: Copy> StaticHook<F> {
+    pub const fn new(module: &'static str, symbol: &'static str, detour: F) -> StaticHook<F> {
+        StaticHook {
+            module,
+            symbol,
+            detour,
+            target: AtomicUsize::new(0),
+            bypass: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn module(&self) -> &'static str {
+        self.module
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        self.symbol
+    }
+
+    /// Finds the function and hooks it, intercepting every thread. Calls
+    /// from the detour through `original` aren't intercepted again.
+    ///
+    /// # Safety
+    ///
+    /// `F` must be the function's true type, and a function pointer.
+    pub unsafe fn install(&'static self) -> Result<Hook, EasyHookError> {
+        assert_eq!(mem::size_of::<F>(), mem::size_of::<*mut c_void>(), "{} isn't hooked with a function pointer", self.symbol);
+        let target = resolve(self.module, self.symbol)?;
+        self.target.store(target as usize, Ordering::SeqCst);
+        let mut hook = Hook::install(target, mem::transmute_copy::<F, *mut c_void>(&self.detour))?;
+        let bypass = hook.bypass_address()?;
+        self.bypass.store(bypass as usize, Ordering::SeqCst);
+        hook.clear_on_uninstall(&self.bypass);
+        Ok(hook)
+    }
+
+    /// The function without the hook: past it while it's installed, or the
+    /// function itself once found and the hook's gone. `None` before
+    /// `install` has found it.
+    pub fn original(&self) -> Option<F> {
+        let address = match self.bypass.load(Ordering::SeqCst) {
+            0 => self.target.load(Ordering::SeqCst),
+            bypass => bypass,
+        };
+        if address == 0 {
+            None
+        } else {
+            Some(unsafe { mem::transmute_copy::<usize, F>(&address) })
+        }
+    }
+}
+
+/// Declares `StaticHook`s, each from its function type, module, symbol and
+/// detour:
+///
+/// ```ignore
+/// static_hooks! {
+///     static RwCameraResize: extern "C" fn(*mut c_void, i32, i32) -> *mut c_void =
+///         ("rw_opengl", "rw_camera_resize") => rw_camera_resize_hook;
+/// }
+/// ```
+#[macro_export]
+macro_rules! static_hooks {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = ($module:expr, $symbol:expr) => $detour:expr;)*) => {
+        $(
+            $(#[$attr])*
+            $vis static $name: $crate::StaticHook<$ty> = $crate::StaticHook::new($module, $symbol, $detour);
+        )*
+    };
+}
+
+/// The address of `symbol` in `module`, loading the module if it isn't yet.
+#[cfg(windows)]
+pub fn resolve(module: &str, symbol: &str) -> Result<*mut c_void, EasyHookError> {
+    use std::ffi::CString;
+
+    use super::wide;
+    use sys;
+
+    let name = wide("LoadLibraryW", 1, module)?;
+    let library = unsafe { sys::LoadLibraryW(name.as_ptr()) };
+    if library.is_null() {
+        return Err(EasyHookError::not_found("LoadLibraryW", format!("unable to load {}", module)));
+    }
+    let name = CString::new(symbol)
+        .map_err(|_| EasyHookError::invalid_argument("GetProcAddress", 2, format!("{:?} contains a nul", symbol)))?;
+    let address = unsafe { sys::GetProcAddress(library, name.as_ptr()) };
+    if address.is_null() {
+        return Err(EasyHookError::not_found("GetProcAddress", format!("{} has no {}", module, symbol)));
+    }
+    Ok(address)
+}
+
+#[cfg(not(windows))]
+pub fn resolve(module: &str, _symbol: &str) -> Result<*mut c_void, EasyHookError> {
+    Err(EasyHookError::not_found("LoadLibraryW", format!("unable to load {} outside Windows", module)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn double(x: i32) -> i32 {
+        x * 2
+    }
+
+    extern "C" fn triple(x: i32) -> i32 {
+        x * 3
+    }
+
+    static_hooks! {
+        static DOUBLE: extern "C" fn(i32) -> i32 = ("maths", "double") => triple;
+    }
+
+    #[test]
+    fn originals_come_from_the_bypass_or_the_target() {
+        assert!(DOUBLE.original().is_none());
+        assert_eq!(DOUBLE.symbol(), "double");
+        DOUBLE.target.store(double as *const () as usize, Ordering::SeqCst);
+        assert_eq!(DOUBLE.original().unwrap()(4), 8);
+        DOUBLE.bypass.store(triple as *const () as usize, Ordering::SeqCst);
+        assert_eq!(DOUBLE.original().unwrap()(4), 12);
+    }
+}
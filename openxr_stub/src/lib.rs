@@ -0,0 +1,359 @@
+//! A null OpenXR runtime, so aw_vr's OpenXR backend can be exercised without a
+//! headset or a real loader.
+//!
+//! Every call succeeds. A session is ready as soon as it is created and focused
+//! as soon as it is begun, the two views sit 64mm apart at standing height, and
+//! every swap chain has three images. `xrStubEndedFrames`,
+//! `xrStubSubmittedLayers` and `xrStubDepthViews` report what `xrEndFrame`
+//! has been handed.
+
+#![allow(non_snake_case)]
+
+#[path = "../../aw_vr/src/backend/openxr/sys.rs"]
+mod sys;
+
+use std::collections::VecDeque;
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::c_char;
+use std::slice;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+const GL_RGBA8: i64 = 0x8058;
+const GL_SRGB8_ALPHA8: i64 = 0x8C43;
+const GL_DEPTH_COMPONENT32F: i64 = 0x8CAC;
+const FRAME_PERIOD: i64 = 11_111_111;
+
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+static NEXT_IMAGE: AtomicU64 = AtomicU64::new(0);
+static DISPLAY_TIME: AtomicU64 = AtomicU64::new(0);
+static ENDED_FRAMES: AtomicU64 = AtomicU64::new(0);
+static SUBMITTED_LAYERS: AtomicU64 = AtomicU64::new(0);
+static DEPTH_VIEWS: AtomicU64 = AtomicU64::new(0);
+static EVENTS: Mutex<VecDeque<(sys::Session, i32)>> = Mutex::new(VecDeque::new());
+
+fn handle() -> u64 {
+    NEXT_HANDLE.fetch_add(1, Ordering::SeqCst)
+}
+
+fn queue_states(session: sys::Session, states: &[i32]) {
+    let mut events = EVENTS.lock().unwrap();
+    for &state in states {
+        events.push_back((session, state));
+    }
+}
+
+/// The first of the two-call idiom's calls only asks for the count.
+unsafe fn fill<T: Copy>(capacity: u32, count: *mut u32, out: *mut T, items: &[T]) -> sys::Result {
+    *count = items.len() as u32;
+    if capacity == 0 {
+        return sys::SUCCESS;
+    }
+    if (capacity as usize) < items.len() {
+        return -11; // XR_ERROR_SIZE_INSUFFICIENT
+    }
+    slice::from_raw_parts_mut(out, items.len()).copy_from_slice(items);
+    sys::SUCCESS
+}
+
+fn posef(x: f32, y: f32, z: f32, yaw: f32) -> sys::Posef {
+    sys::Posef {
+        orientation: sys::Quaternionf { x: 0.0, y: (yaw / 2.0).sin(), z: 0.0, w: (yaw / 2.0).cos() },
+        position: sys::Vector3f { x, y, z },
+    }
+}
+
+unsafe extern "system" fn xrEnumerateInstanceExtensionProperties(_layer: *const c_char, capacity: u32, count: *mut u32, properties: *mut sys::ExtensionProperties) -> sys::Result {
+    let extension = |name: &[u8]| {
+        let mut property: sys::ExtensionProperties = mem::zeroed();
+        property.ty = sys::TYPE_EXTENSION_PROPERTIES;
+        property.extensionVersion = 1;
+        for (dst, &src) in property.extensionName.iter_mut().zip(name) {
+            *dst = src as c_char;
+        }
+        property
+    };
+    fill(capacity, count, properties, &[
+        extension(sys::KHR_OPENGL_ENABLE_EXTENSION_NAME),
+        extension(sys::KHR_COMPOSITION_LAYER_DEPTH_EXTENSION_NAME),
+    ])
+}
+
+unsafe extern "system" fn xrCreateInstance(_info: *const sys::InstanceCreateInfo, instance: *mut sys::Instance) -> sys::Result {
+    *instance = handle();
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrDestroyInstance(_instance: sys::Instance) -> sys::Result {
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrPollEvent(_instance: sys::Instance, buffer: *mut sys::EventDataBuffer) -> sys::Result {
+    match EVENTS.lock().unwrap().pop_front() {
+        Some((session, state)) => {
+            let event = buffer as *mut sys::EventDataSessionStateChanged;
+            (*event).ty = sys::TYPE_EVENT_DATA_SESSION_STATE_CHANGED;
+            (*event).session = session;
+            (*event).state = state;
+            (*event).time = DISPLAY_TIME.load(Ordering::SeqCst) as i64;
+            sys::SUCCESS
+        },
+        None => sys::EVENT_UNAVAILABLE,
+    }
+}
+
+unsafe extern "system" fn xrStringToPath(_instance: sys::Instance, _name: *const c_char, path: *mut sys::Path) -> sys::Result {
+    *path = handle();
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrGetSystem(_instance: sys::Instance, _info: *const sys::SystemGetInfo, system: *mut sys::SystemId) -> sys::Result {
+    *system = 1;
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrEnumerateViewConfigurationViews(_instance: sys::Instance, _system: sys::SystemId, _kind: i32, capacity: u32, count: *mut u32, views: *mut sys::ViewConfigurationView) -> sys::Result {
+    let view = sys::ViewConfigurationView {
+        ty: sys::TYPE_VIEW_CONFIGURATION_VIEW,
+        next: std::ptr::null_mut(),
+        recommendedImageRectWidth: 1440,
+        maxImageRectWidth: 4096,
+        recommendedImageRectHeight: 1600,
+        maxImageRectHeight: 4096,
+        recommendedSwapchainSampleCount: 1,
+        maxSwapchainSampleCount: 4,
+    };
+    fill(capacity, count, views, &[view, view])
+}
+
+unsafe extern "system" fn xrGetOpenGLGraphicsRequirementsKHR(_instance: sys::Instance, _system: sys::SystemId, requirements: *mut sys::GraphicsRequirementsOpenGLKHR) -> sys::Result {
+    (*requirements).minApiVersionSupported = 1 << 48;
+    (*requirements).maxApiVersionSupported = 4 << 48 | 6 << 32;
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrCreateSession(_instance: sys::Instance, _info: *const sys::SessionCreateInfo, session: *mut sys::Session) -> sys::Result {
+    *session = handle();
+    queue_states(*session, &[sys::SESSION_STATE_IDLE, sys::SESSION_STATE_READY]);
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrDestroySession(_session: sys::Session) -> sys::Result {
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrBeginSession(session: sys::Session, _info: *const sys::SessionBeginInfo) -> sys::Result {
+    queue_states(session, &[sys::SESSION_STATE_SYNCHRONIZED, sys::SESSION_STATE_VISIBLE, sys::SESSION_STATE_FOCUSED]);
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrEndSession(_session: sys::Session) -> sys::Result {
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrCreateReferenceSpace(_session: sys::Session, _info: *const sys::ReferenceSpaceCreateInfo, space: *mut sys::Space) -> sys::Result {
+    *space = handle();
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrCreateActionSpace(_session: sys::Session, _info: *const sys::ActionSpaceCreateInfo, space: *mut sys::Space) -> sys::Result {
+    *space = handle();
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrDestroySpace(_space: sys::Space) -> sys::Result {
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrLocateSpace(_space: sys::Space, _base: sys::Space, _time: sys::Time, location: *mut sys::SpaceLocation) -> sys::Result {
+    (*location).locationFlags = sys::SPACE_LOCATION_ORIENTATION_VALID_BIT | sys::SPACE_LOCATION_POSITION_VALID_BIT;
+    (*location).pose = posef(0.25, 1.6, -0.5, std::f32::consts::FRAC_PI_2);
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrEnumerateSwapchainFormats(_session: sys::Session, capacity: u32, count: *mut u32, formats: *mut i64) -> sys::Result {
+    fill(capacity, count, formats, &[GL_RGBA8, GL_SRGB8_ALPHA8, GL_DEPTH_COMPONENT32F])
+}
+
+unsafe extern "system" fn xrCreateSwapchain(_session: sys::Session, _info: *const sys::SwapchainCreateInfo, swapchain: *mut sys::Swapchain) -> sys::Result {
+    *swapchain = handle();
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrDestroySwapchain(_swapchain: sys::Swapchain) -> sys::Result {
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrEnumerateSwapchainImages(_swapchain: sys::Swapchain, capacity: u32, count: *mut u32, images: *mut sys::SwapchainImageOpenGLKHR) -> sys::Result {
+    let mut stub_images = [sys::SwapchainImageOpenGLKHR { ty: sys::TYPE_SWAPCHAIN_IMAGE_OPENGL_KHR, next: std::ptr::null_mut(), image: 0 }; 3];
+    for (index, image) in stub_images.iter_mut().enumerate() {
+        image.image = index as u32 + 1;
+    }
+    fill(capacity, count, images, &stub_images)
+}
+
+unsafe extern "system" fn xrAcquireSwapchainImage(_swapchain: sys::Swapchain, _info: *const sys::SwapchainImageAcquireInfo, index: *mut u32) -> sys::Result {
+    *index = (NEXT_IMAGE.fetch_add(1, Ordering::SeqCst) % 3) as u32;
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrWaitSwapchainImage(_swapchain: sys::Swapchain, _info: *const sys::SwapchainImageWaitInfo) -> sys::Result {
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrReleaseSwapchainImage(_swapchain: sys::Swapchain, _info: *const sys::SwapchainImageReleaseInfo) -> sys::Result {
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrWaitFrame(_session: sys::Session, _info: *const sys::FrameWaitInfo, state: *mut sys::FrameState) -> sys::Result {
+    let time = DISPLAY_TIME.fetch_add(FRAME_PERIOD as u64, Ordering::SeqCst) as i64 + FRAME_PERIOD;
+    (*state).predictedDisplayTime = time;
+    (*state).predictedDisplayPeriod = FRAME_PERIOD;
+    (*state).shouldRender = 1;
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrBeginFrame(_session: sys::Session, _info: *const sys::FrameBeginInfo) -> sys::Result {
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrEndFrame(_session: sys::Session, info: *const sys::FrameEndInfo) -> sys::Result {
+    ENDED_FRAMES.fetch_add(1, Ordering::SeqCst);
+    SUBMITTED_LAYERS.fetch_add(u64::from((*info).layerCount), Ordering::SeqCst);
+    for &layer in slice::from_raw_parts((*info).layers, (*info).layerCount as usize) {
+        if (*layer).ty != sys::TYPE_COMPOSITION_LAYER_PROJECTION {
+            continue;
+        }
+        let layer = &*(layer as *const sys::CompositionLayerProjection);
+        for view in slice::from_raw_parts(layer.views, layer.viewCount as usize) {
+            let depth = view.next as *const sys::CompositionLayerDepthInfoKHR;
+            if !depth.is_null() && (*depth).ty == sys::TYPE_COMPOSITION_LAYER_DEPTH_INFO_KHR {
+                DEPTH_VIEWS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrLocateViews(_session: sys::Session, _info: *const sys::ViewLocateInfo, state: *mut sys::ViewState, capacity: u32, count: *mut u32, views: *mut sys::View) -> sys::Result {
+    (*state).viewStateFlags = 0xF;
+    let fov = sys::Fovf { angleLeft: -0.8, angleRight: 0.7, angleUp: 0.75, angleDown: -0.85 };
+    let view = |x| sys::View { ty: sys::TYPE_VIEW, next: std::ptr::null_mut(), pose: posef(x, 1.6, 0.0, 0.0), fov };
+    fill(capacity, count, views, &[view(-0.032), view(0.032)])
+}
+
+unsafe extern "system" fn xrCreateActionSet(_instance: sys::Instance, _info: *const sys::ActionSetCreateInfo, set: *mut sys::ActionSet) -> sys::Result {
+    *set = handle();
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrCreateAction(_set: sys::ActionSet, _info: *const sys::ActionCreateInfo, action: *mut sys::Action) -> sys::Result {
+    *action = handle();
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrSuggestInteractionProfileBindings(_instance: sys::Instance, _bindings: *const sys::InteractionProfileSuggestedBinding) -> sys::Result {
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrAttachSessionActionSets(_session: sys::Session, _info: *const sys::SessionActionSetsAttachInfo) -> sys::Result {
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrSyncActions(_session: sys::Session, _info: *const sys::ActionsSyncInfo) -> sys::Result {
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrGetActionStateBoolean(_session: sys::Session, _info: *const sys::ActionStateGetInfo, state: *mut sys::ActionStateBoolean) -> sys::Result {
+    (*state).currentState = 0;
+    (*state).isActive = 1;
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrGetActionStateFloat(_session: sys::Session, _info: *const sys::ActionStateGetInfo, state: *mut sys::ActionStateFloat) -> sys::Result {
+    (*state).currentState = 0.0;
+    (*state).isActive = 1;
+    sys::SUCCESS
+}
+
+unsafe extern "system" fn xrGetActionStateVector2f(_session: sys::Session, _info: *const sys::ActionStateGetInfo, state: *mut sys::ActionStateVector2f) -> sys::Result {
+    (*state).currentState = sys::Vector2f::default();
+    (*state).isActive = 1;
+    sys::SUCCESS
+}
+
+macro_rules! lookup {
+    ($name:expr; $($function:ident,)*) => {
+        match $name {
+            $(stringify!($function) => Some(mem::transmute::<sys::pfn::$function, sys::VoidFunction>($function)),)*
+            _ => None,
+        }
+    }
+}
+
+/// The loader entry point; every other function is reached through it.
+///
+/// # Safety
+///
+/// `name` must be a NUL-terminated string and `function` valid for writes.
+#[no_mangle]
+pub unsafe extern "system" fn xrGetInstanceProcAddr(_instance: sys::Instance, name: *const c_char, function: *mut Option<sys::VoidFunction>) -> sys::Result {
+    *function = lookup! { CStr::from_ptr(name).to_str().unwrap_or("");
+        xrEnumerateInstanceExtensionProperties,
+        xrCreateInstance,
+        xrDestroyInstance,
+        xrPollEvent,
+        xrStringToPath,
+        xrGetSystem,
+        xrEnumerateViewConfigurationViews,
+        xrGetOpenGLGraphicsRequirementsKHR,
+        xrCreateSession,
+        xrDestroySession,
+        xrBeginSession,
+        xrEndSession,
+        xrCreateReferenceSpace,
+        xrCreateActionSpace,
+        xrDestroySpace,
+        xrLocateSpace,
+        xrEnumerateSwapchainFormats,
+        xrCreateSwapchain,
+        xrDestroySwapchain,
+        xrEnumerateSwapchainImages,
+        xrAcquireSwapchainImage,
+        xrWaitSwapchainImage,
+        xrReleaseSwapchainImage,
+        xrWaitFrame,
+        xrBeginFrame,
+        xrEndFrame,
+        xrLocateViews,
+        xrCreateActionSet,
+        xrCreateAction,
+        xrSuggestInteractionProfileBindings,
+        xrAttachSessionActionSets,
+        xrSyncActions,
+        xrGetActionStateBoolean,
+        xrGetActionStateFloat,
+        xrGetActionStateVector2f,
+    };
+    if (*function).is_some() {
+        sys::SUCCESS
+    } else {
+        sys::ERROR_FUNCTION_UNSUPPORTED
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn xrStubEndedFrames() -> u64 {
+    ENDED_FRAMES.load(Ordering::SeqCst)
+}
+
+#[no_mangle]
+pub extern "system" fn xrStubSubmittedLayers() -> u64 {
+    SUBMITTED_LAYERS.load(Ordering::SeqCst)
+}
+
+#[no_mangle]
+pub extern "system" fn xrStubDepthViews() -> u64 {
+    DEPTH_VIEWS.load(Ordering::SeqCst)
+}